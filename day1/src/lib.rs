@@ -16,7 +16,7 @@ pub fn most_calories(filename: String, elf_count: usize) -> i32 {
     cals_per_elf.sort();
     cals_per_elf.reverse();
 
-    return cals_per_elf[..elf_count].iter().sum::<i32>();
+    cals_per_elf[..elf_count].iter().sum::<i32>()
 }
 
 #[cfg(test)]
@@ -0,0 +1,253 @@
+use super::{isa, Cpu, Instruction, State, ISA};
+use nom::combinator::all_consuming;
+use nom::Finish;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Text that is not an instruction, with 1-based line and column numbers.
+    Syntax {
+        line: usize,
+        column: usize,
+        found: String,
+    },
+    UnknownOpcode {
+        offset: usize,
+        byte: u8,
+    },
+    InvalidOperand {
+        offset: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Syntax {
+                line,
+                column,
+                found,
+            } => {
+                if found.is_empty() {
+                    write!(f, "{}:{}: Unexpected end of line", line, column)
+                } else {
+                    write!(f, "{}:{}: Unexpected `{}`", line, column, found)
+                }
+            }
+            Error::UnknownOpcode { offset, byte } => {
+                write!(f, "Unknown opcode {:#04x} at offset {}", byte, offset)
+            }
+            Error::InvalidOperand { offset } => write!(f, "Invalid operand at offset {}", offset),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Parses one instruction per line.
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, Error> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            all_consuming(isa::parse_instruction)(line)
+                .finish()
+                .map(|(_, instruction)| instruction)
+                .map_err(|error| {
+                    let rest = error.input;
+                    Error::Syntax {
+                        line: i + 1,
+                        column: line.len() - rest.len() + 1,
+                        found: rest.split_whitespace().next().unwrap_or("").to_string(),
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Lists instructions in the format `assemble` parses.
+pub fn disassemble(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(|instruction| format!("{}\n", instruction))
+        .collect()
+}
+
+/// Cycles `annotate` runs a program for at most.
+const ANNOTATE_CYCLES: usize = 100_000;
+
+/// Lists instructions along with the cycle each first starts in and the value of X during it,
+/// running the program until it halts, loops or would run for over `ANNOTATE_CYCLES` cycles.
+/// Instructions not reached by then have no annotation, and a last line tells when the program
+/// got cut short.
+pub fn annotate(instructions: &[Instruction]) -> String {
+    let mut first = vec![None; instructions.len()];
+    let mut cpu = Cpu::new(instructions.to_vec()).with_observer(|state: &State| {
+        first[state.pc].get_or_insert((state.cycle, state.registers.x));
+    });
+    let exit =
+        cpu.run_to(|cpu| cpu.cycle() + cpu.instructions()[cpu.pc()].cycles() > ANNOTATE_CYCLES);
+    let cycles = cpu.cycle();
+    drop(cpu);
+
+    let mut output = format!("{:>6} {:>6}  instruction\n", "cycle", "x");
+    for (instruction, first) in instructions.iter().zip(first) {
+        output += match first {
+            Some((cycle, x)) => format!("{:>6} {:>6}  {}\n", cycle, x, instruction),
+            None => format!("{:>6} {:>6}  {}\n", "-", "-", instruction),
+        }
+        .as_str();
+    }
+    if exit.is_none() {
+        output += format!("Stopped after {} cycles\n", cycles).as_str();
+    }
+    output
+}
+
+/// Encodes every instruction as its index in the instruction set, followed by its operand, if
+/// any, as a zigzag LEB128 variable-length integer.
+pub fn encode(instructions: &[Instruction]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for instruction in instructions {
+        let index = ISA
+            .iter()
+            .position(|spec| spec.opcode == instruction.opcode)
+            .unwrap();
+        bytes.push(index as u8);
+        if !instruction.opcode.spec().has_operand {
+            continue;
+        }
+        let operand = instruction.operand as i64;
+        let mut value = ((operand << 1) ^ (operand >> 63)) as u64;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Instruction>, Error> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let byte = bytes[offset];
+        let spec = ISA
+            .get(byte as usize)
+            .ok_or(Error::UnknownOpcode { offset, byte })?;
+        offset += 1;
+
+        let mut value = 0u64;
+        if spec.has_operand {
+            let mut shift = 0;
+            loop {
+                let byte = *bytes.get(offset).ok_or(Error::InvalidOperand { offset })?;
+                if shift >= 64 {
+                    return Err(Error::InvalidOperand { offset });
+                }
+                value |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                offset += 1;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+        let operand = ((value >> 1) as i64 ^ -((value & 1) as i64)) as isize;
+        instructions.push(Instruction::new(spec.opcode, operand));
+    }
+    Ok(instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Opcode;
+    use std::fs;
+    use test_case::test_case;
+
+    #[test]
+    fn round_trip() {
+        let source = fs::read_to_string("assets/example.txt").unwrap();
+        let instructions = assemble(&source).unwrap();
+        assert_eq!(disassemble(&instructions), source);
+
+        let bytes = encode(&instructions);
+        // One byte per instruction and one per operand, all of them small.
+        assert_eq!(bytes.len(), 146 + 94);
+        assert_eq!(decode(&bytes).unwrap(), instructions);
+
+        let source = fs::read_to_string("assets/example_isa.txt").unwrap();
+        let instructions = assemble(&source).unwrap();
+        assert_eq!(decode(&encode(&instructions)).unwrap(), instructions);
+    }
+
+    #[test_case(isize::MIN, 10)]
+    #[test_case(-65, 2)]
+    #[test_case(-64, 1)]
+    #[test_case(63, 1)]
+    #[test_case(isize::MAX, 10)]
+    fn operand(operand: isize, len: usize) {
+        let instructions = vec![Instruction::new(Opcode::Addx, operand)];
+        let bytes = encode(&instructions);
+        assert_eq!(bytes.len(), 1 + len);
+        assert_eq!(decode(&bytes).unwrap(), instructions);
+    }
+
+    #[test_case("noop\naddx\n", "2:5: Unexpected end of line")]
+    #[test_case("noop\n  addx 1\n", "2:1: Unexpected `addx`")]
+    #[test_case("addx 1\naddx 1x\n", "2:7: Unexpected `x`")]
+    #[test_case("subx 1", "1:1: Unexpected `subx`")]
+    fn syntax_error(source: &str, expected: &str) {
+        assert_eq!(assemble(source).unwrap_err().to_string(), expected);
+    }
+
+    #[test_case(&[8], "Unknown opcode 0x08 at offset 0")]
+    #[test_case(&[0, 1, 0x80], "Invalid operand at offset 3")]
+    fn decode_error(bytes: &[u8], expected: &str) {
+        assert_eq!(decode(bytes).unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn annotated_forever() {
+        let source = "addx 1\njmp -1\nnoop\n";
+        assert_eq!(
+            annotate(&assemble(source).unwrap()),
+            " cycle      x  instruction
+     1      1  addx 1
+     3      2  jmp -1
+     -      -  noop
+Stopped after 99999 cycles
+"
+        );
+    }
+
+    #[test]
+    fn annotated() {
+        let source = "noop\naddx 3\njmp 2\naddx -5\nnoop\n";
+        assert_eq!(
+            annotate(&assemble(source).unwrap()),
+            " cycle      x  instruction
+     1      1  noop
+     2      1  addx 3
+     4      4  jmp 2
+     -      -  addx -5
+     5      4  noop
+"
+        );
+    }
+}
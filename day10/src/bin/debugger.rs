@@ -0,0 +1,29 @@
+use day10::{parse_instructions, Debugger, Reply};
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+fn main() {
+    let filename = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "day10/assets/input.txt".to_string());
+    let instructions = match parse_instructions(&filename) {
+        Ok(instructions) => instructions,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            process::exit(1);
+        }
+    };
+
+    let mut debugger = Debugger::new(instructions);
+    let mut stdout = io::stdout();
+    println!("Debugging {}, type help for commands.", filename);
+    for line in io::stdin().lock().lines() {
+        match debugger.execute(&line.unwrap()) {
+            Ok(Reply::Output(output)) => print!("{}", output),
+            Ok(Reply::Quit) => break,
+            Err(error) => println!("Error: {}", error),
+        }
+        stdout.flush().unwrap();
+    }
+}
@@ -0,0 +1,302 @@
+use super::{Flow, Instruction};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Registers {
+    pub x: isize,
+    pub y: isize,
+}
+
+/// Why a program stopped running.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Exit {
+    /// It ran out of instructions or reached a `halt`.
+    Halted,
+    /// It reached an instruction with the same registers as before, so would repeat the cycles
+    /// since then forever. `start` is the first cycle of the loop. Loops that change the registers
+    /// on every iteration, such as `addx 1` then `jmp -1`, only repeat once the registers wrap
+    /// around, so in practice never get detected.
+    Loop { start: usize, length: usize },
+}
+
+/// The machine during a cycle, which counts from 1.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct State {
+    pub cycle: usize,
+    pub pc: usize,
+    pub registers: Registers,
+}
+
+/// Gets called during every cycle, before the instruction in progress takes effect.
+pub trait Observer {
+    fn on_cycle(&mut self, state: &State);
+}
+
+impl Observer for () {
+    fn on_cycle(&mut self, _: &State) {}
+}
+
+impl<F: FnMut(&State)> Observer for F {
+    fn on_cycle(&mut self, state: &State) {
+        self(state)
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn on_cycle(&mut self, state: &State) {
+        self.0.on_cycle(state);
+        self.1.on_cycle(state);
+    }
+}
+
+/// Runs instructions one cycle at a time, each instruction taking effect at the end of its last
+/// cycle.
+pub struct Cpu<O: Observer = ()> {
+    instructions: Vec<Instruction>,
+    registers: Registers,
+    pc: usize,
+    /// Cycles completed so far.
+    cycle: usize,
+    /// Cycles spent on the instruction in progress.
+    elapsed: usize,
+    halted: bool,
+    observer: O,
+}
+
+impl Cpu {
+    pub fn new(instructions: Vec<Instruction>) -> Cpu {
+        Cpu {
+            instructions,
+            registers: Registers { x: 1, y: 0 },
+            pc: 0,
+            cycle: 0,
+            elapsed: 0,
+            halted: false,
+            observer: (),
+        }
+    }
+}
+
+impl<O: Observer> Cpu<O> {
+    pub fn with_observer<P: Observer>(self, observer: P) -> Cpu<P> {
+        Cpu {
+            instructions: self.instructions,
+            registers: self.registers,
+            pc: self.pc,
+            cycle: self.cycle,
+            elapsed: self.elapsed,
+            halted: self.halted,
+            observer,
+        }
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.registers
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Cycles completed so far.
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Cycles spent on the instruction in progress, 0 between instructions.
+    pub fn elapsed(&self) -> usize {
+        self.elapsed
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted || self.pc >= self.instructions.len()
+    }
+
+    /// Runs a single cycle, returning whether there was an instruction to run.
+    pub fn step(&mut self) -> bool {
+        if self.halted() {
+            return false;
+        }
+        self.observer.on_cycle(&State {
+            cycle: self.cycle + 1,
+            pc: self.pc,
+            registers: self.registers,
+        });
+        self.cycle += 1;
+        self.elapsed += 1;
+
+        let instruction = self.instructions[self.pc];
+        if self.elapsed == instruction.cycles() {
+            self.elapsed = 0;
+            match instruction.execute(&mut self.registers) {
+                Flow::Next => self.pc += 1,
+                // Jumping before the first instruction halts like jumping past the last one.
+                Flow::Jump(offset) => match self.pc.checked_add_signed(offset) {
+                    Some(pc) => self.pc = pc,
+                    None => self.halted = true,
+                },
+                Flow::Halt => self.halted = true,
+            }
+        }
+        true
+    }
+
+    /// Runs the rest of the instruction in progress, or the next one, returning whether there was
+    /// an instruction to run.
+    pub fn step_instruction(&mut self) -> bool {
+        if !self.step() {
+            return false;
+        }
+        while self.elapsed > 0 {
+            self.step();
+        }
+        true
+    }
+
+    /// Runs until `cycle` cycles are completed, returning whether it got there before halting.
+    pub fn run_until(&mut self, cycle: usize) -> bool {
+        while self.cycle < cycle {
+            if !self.step() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Runs until halting or until getting stuck in a loop that leaves the registers unchanged
+    /// from one iteration to the next, stopping wherever in the loop that got detected.
+    pub fn run(&mut self) -> Exit {
+        self.run_to(|_| false).unwrap()
+    }
+
+    /// Runs like `run` does, but also stops before starting any instruction but the first for
+    /// which `stop` returns true, returning `None` then.
+    pub fn run_to(&mut self, mut stop: impl FnMut(&Self) -> bool) -> Option<Exit> {
+        // Brent's cycle detection over the states instructions start in, keeping memory constant:
+        // the state saved after every power of two instructions gets compared to the later ones.
+        let origin = self.cycle;
+        let mut first = None;
+        let mut saved = None;
+        let (mut power, mut length) = (1, 0);
+        loop {
+            if self.halted() {
+                return Some(Exit::Halted);
+            }
+            if self.elapsed == 0 {
+                if self.cycle > origin && stop(self) {
+                    return None;
+                }
+                let state = (self.pc, self.registers);
+                match first {
+                    None => {
+                        first = Some(State {
+                            cycle: self.cycle + 1,
+                            pc: self.pc,
+                            registers: self.registers,
+                        });
+                        saved = Some(state);
+                    }
+                    Some(first) => {
+                        length += 1;
+                        if saved == Some(state) {
+                            let (start, length) = self.find_loop(first, length);
+                            return Some(Exit::Loop { start, length });
+                        }
+                        if length == power {
+                            saved = Some(state);
+                            power *= 2;
+                            length = 0;
+                        }
+                    }
+                }
+            }
+            self.step();
+        }
+    }
+
+    /// Replays the program from the state of its first instruction to find the first cycle and
+    /// the length in cycles of a loop of `length` instructions.
+    fn find_loop(&self, first: State, length: usize) -> (usize, usize) {
+        let replay = || Cpu {
+            instructions: self.instructions.clone(),
+            registers: first.registers,
+            pc: first.pc,
+            cycle: first.cycle - 1,
+            elapsed: 0,
+            halted: false,
+            observer: (),
+        };
+        let (mut start, mut end) = (replay(), replay());
+        for _ in 0..length {
+            end.step_instruction();
+        }
+        while (start.pc, start.registers) != (end.pc, end.registers) {
+            start.step_instruction();
+            end.step_instruction();
+        }
+        (start.cycle + 1, end.cycle - start.cycle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_instructions, Opcode};
+    use test_case::test_case;
+
+    #[test]
+    fn step() {
+        let instructions = vec![
+            Instruction::new(Opcode::Noop, 0),
+            Instruction::new(Opcode::Addx, 3),
+            Instruction::new(Opcode::Addx, -5),
+        ];
+        let mut states = Vec::new();
+        let mut cpu = Cpu::new(instructions).with_observer(|state: &State| states.push(*state));
+        assert!(cpu.run_until(3));
+        assert_eq!((cpu.cycle(), cpu.pc(), cpu.registers().x), (3, 2, 4));
+        assert_eq!(cpu.run(), Exit::Halted);
+        assert!(cpu.halted() && !cpu.step() && !cpu.run_until(10));
+        assert_eq!((cpu.cycle(), cpu.registers().x), (5, -1));
+        drop(cpu);
+
+        let xs = states
+            .iter()
+            .map(|state| (state.cycle, state.pc, state.registers.x))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            xs,
+            vec![(1, 0, 1), (2, 1, 1), (3, 1, 1), (4, 2, 4), (5, 2, 4)]
+        );
+    }
+
+    #[test_case("assets/example_isa.txt", Exit::Halted, 1, 63)]
+    #[test_case("assets/example_loop.txt", Exit::Loop { start: 4, length: 5 }, 3, 1)]
+    fn run(filename: &str, exit: Exit, x: isize, y: isize) {
+        let mut cpu = Cpu::new(parse_instructions(filename).unwrap());
+        assert_eq!(cpu.run(), exit);
+        assert_eq!(cpu.registers(), Registers { x, y });
+    }
+
+    #[test_case("mulx -1\njmp -1", Exit::Loop { start: 1, length: 10 })]
+    #[test_case("addx -1\njz 2\njmp -2\nhalt", Exit::Halted)]
+    fn loops(source: &str, exit: Exit) {
+        let mut cpu = Cpu::new(assemble(source).unwrap());
+        assert_eq!(cpu.run(), exit);
+    }
+}
@@ -0,0 +1,236 @@
+use super::{Observer, State};
+use std::fmt;
+use std::io::Write;
+
+const LETTER_WIDTH: usize = 4;
+const LETTER_HEIGHT: usize = 6;
+
+/// Block letters as rendered on the CRT, one row per byte with the leftmost pixel in bit 3.
+const LETTERS: [(char, [u8; LETTER_HEIGHT]); 18] = [
+    ('A', [0b0110, 0b1001, 0b1001, 0b1111, 0b1001, 0b1001]),
+    ('B', [0b1110, 0b1001, 0b1110, 0b1001, 0b1001, 0b1110]),
+    ('C', [0b0110, 0b1001, 0b1000, 0b1000, 0b1001, 0b0110]),
+    ('E', [0b1111, 0b1000, 0b1110, 0b1000, 0b1000, 0b1111]),
+    ('F', [0b1111, 0b1000, 0b1110, 0b1000, 0b1000, 0b1000]),
+    ('G', [0b0110, 0b1001, 0b1000, 0b1011, 0b1001, 0b0111]),
+    ('H', [0b1001, 0b1001, 0b1111, 0b1001, 0b1001, 0b1001]),
+    ('I', [0b0111, 0b0010, 0b0010, 0b0010, 0b0010, 0b0111]),
+    ('J', [0b0011, 0b0001, 0b0001, 0b0001, 0b1001, 0b0110]),
+    ('K', [0b1001, 0b1010, 0b1100, 0b1010, 0b1010, 0b1001]),
+    ('L', [0b1000, 0b1000, 0b1000, 0b1000, 0b1000, 0b1111]),
+    ('O', [0b0110, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110]),
+    ('P', [0b1110, 0b1001, 0b1001, 0b1110, 0b1000, 0b1000]),
+    ('R', [0b1110, 0b1001, 0b1001, 0b1110, 0b1010, 0b1001]),
+    ('S', [0b0111, 0b1000, 0b1000, 0b0110, 0b0001, 0b1110]),
+    ('U', [0b1001, 0b1001, 0b1001, 0b1001, 0b1001, 0b0110]),
+    ('Z', [0b1111, 0b0001, 0b0010, 0b0100, 0b1000, 0b1111]),
+    (' ', [0b0000, 0b0000, 0b0000, 0b0000, 0b0000, 0b0000]),
+];
+
+/// Draws a pixel every cycle, lit when the sprite centered on X covers it. A screen with a height
+/// starts over from the top once full, while one without grows a row at a time.
+pub struct Crt {
+    width: usize,
+    height: Option<usize>,
+    sprite_width: usize,
+    pixels: Vec<bool>,
+    drawn: usize,
+}
+
+impl Crt {
+    /// The puzzle's CRT, with a 3 pixels wide sprite.
+    pub fn new(width: usize) -> Crt {
+        Crt::with_size(width, None, 3)
+    }
+
+    /// A CRT whose sprite covers X and `sprite_width - 1` pixels around it, one more of them on
+    /// the right when even.
+    pub fn with_size(width: usize, height: Option<usize>, sprite_width: usize) -> Crt {
+        assert!(width > 0, "Width must be positive");
+        assert!(height != Some(0), "Height must be positive");
+        Crt {
+            width,
+            height,
+            sprite_width,
+            pixels: Vec::new(),
+            drawn: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Rows drawn so far, including the one in progress.
+    pub fn rows(&self) -> usize {
+        self.pixels.len().div_ceil(self.width)
+    }
+
+    /// Cycles drawn so far.
+    pub fn drawn(&self) -> usize {
+        self.drawn
+    }
+
+    pub fn is_lit(&self, row: usize, col: usize) -> bool {
+        col < self.width && self.pixels.get(row * self.width + col) == Some(&true)
+    }
+
+    /// Writes the screen as a grayscale PNG with `scale` pixels per CRT pixel.
+    pub fn write_png(&self, writer: impl Write, scale: usize) -> Result<(), png::EncodingError> {
+        let (width, height) = (self.width * scale, self.rows() * scale);
+        let mut image = vec![0; width * height];
+        for (y, row) in image.chunks_mut(width.max(1)).enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if self.is_lit(y / scale, x / scale) {
+                    *pixel = 255;
+                }
+            }
+        }
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image)
+    }
+
+    /// Reads the block letters on the screen, 5 pixels apart, with every 6 rows making a line of
+    /// text. Unknown letters read as `?`.
+    pub fn read(&self) -> String {
+        let lines = (0..self.rows() / LETTER_HEIGHT).map(|line| {
+            (0..(self.width + 1) / (LETTER_WIDTH + 1))
+                .map(|letter| {
+                    let rows = (0..LETTER_HEIGHT).map(|y| {
+                        (0..LETTER_WIDTH).fold(0, |bits, x| {
+                            let lit = self
+                                .is_lit(line * LETTER_HEIGHT + y, letter * (LETTER_WIDTH + 1) + x);
+                            bits << 1 | lit as u8
+                        })
+                    });
+                    let rows = rows.collect::<Vec<u8>>();
+                    match LETTERS.iter().find(|(_, glyph)| *glyph == rows.as_slice()) {
+                        Some((c, _)) => *c,
+                        None => '?',
+                    }
+                })
+                .collect::<String>()
+        });
+        lines.collect::<Vec<String>>().join("\n")
+    }
+}
+
+impl Observer for Crt {
+    fn on_cycle(&mut self, state: &State) {
+        let index = match self.height {
+            Some(height) => self.drawn % (self.width * height),
+            None => self.drawn,
+        };
+        let pixel = (index % self.width) as isize;
+        let x = state.registers.x;
+        let sprite = x - (self.sprite_width as isize - 1) / 2..=x + self.sprite_width as isize / 2;
+        let lit = self.sprite_width > 0 && sprite.contains(&pixel);
+        if index < self.pixels.len() {
+            self.pixels[index] = lit;
+        } else {
+            self.pixels.push(lit);
+        }
+        self.drawn += 1;
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, pixel) in self.pixels.iter().enumerate() {
+            write!(f, "{}", if *pixel { "#" } else { "." })?;
+            if i % self.width == self.width - 1 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_instructions, Cpu, Registers};
+    use test_case::test_case;
+
+    fn run(filename: &str, crt: Crt) -> Crt {
+        let mut cpu = Cpu::new(parse_instructions(filename).unwrap()).with_observer(crt);
+        cpu.run();
+        cpu.into_observer()
+    }
+
+    /// A CRT showing a picture of `#` and `.` pixels, with X on each lit pixel as it gets drawn
+    /// and a screen's width away otherwise.
+    fn draw(picture: &str) -> Crt {
+        let rows = picture.trim().lines().collect::<Vec<&str>>();
+        let mut crt = Crt::new(rows[0].len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                let offset = if pixel == '#' { 0 } else { crt.width() };
+                crt.on_cycle(&State {
+                    cycle: y * crt.width() + x + 1,
+                    pc: 0,
+                    registers: Registers {
+                        x: (x + offset) as isize,
+                        y: 0,
+                    },
+                });
+            }
+        }
+        crt
+    }
+
+    #[test]
+    fn read() {
+        // The third letter is not in the font.
+        let picture = "
+#..#..###.#..#
+#..#...#..#..#
+####...#...##.
+#..#...#...##.
+#..#...#..#..#
+#..#..###.#..#
+#.....##......
+#....#..#.....
+#....#..#.....
+#....#..#.....
+#....#..#.....
+####..##......
+";
+        let crt = draw(picture);
+        assert_eq!(crt.to_string().trim(), picture.trim());
+        assert_eq!(crt.read(), "HI?\nLO ");
+
+        let crt = run("assets/example.txt", Crt::new(40));
+        assert_eq!(crt.read(), "????????");
+    }
+
+    #[test_case(None, 3, 30, "##..##..##\n....##....\n..........\n")]
+    #[test_case(None, 1, 20, ".#...#..#.\n....#.....\n")]
+    #[test_case(None, 2, 25, ".#...#..##\n....##....\n.....")]
+    #[test_case(Some(1), 4, 15, "....##..##\n")]
+    fn size(height: Option<usize>, sprite_width: usize, cycles: usize, expected: &str) {
+        let crt = Crt::with_size(10, height, sprite_width);
+        let mut cpu =
+            Cpu::new(parse_instructions("assets/example.txt").unwrap()).with_observer(crt);
+        cpu.run_until(cycles);
+        assert_eq!(cpu.observer().to_string(), expected);
+        assert_eq!(cpu.observer().drawn(), cycles);
+    }
+
+    #[test]
+    fn png() {
+        let crt = run("assets/example.txt", Crt::new(40));
+        let mut data = Vec::new();
+        crt.write_png(&mut data, 3).unwrap();
+
+        let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        assert_eq!((info.width, info.height), (120, 18));
+        assert_eq!(&image[0..8], &[255, 255, 255, 255, 255, 255, 0, 0]);
+        assert_eq!(image[120 * 3 + 6], 255);
+    }
+}
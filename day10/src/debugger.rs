@@ -0,0 +1,430 @@
+use super::{Cpu, Crt, Exit, Instruction};
+use std::fmt;
+
+/// Cycles a `continue` without a target cycle runs for at most.
+const CONTINUE_CYCLES: usize = 1_000_000;
+
+const HELP: &str = "\
+step [n]          run the next n instructions, 1 by default
+tick [n]          run the next n cycles, 1 by default
+continue [cycle]  run until a breakpoint, halting, or until the given cycle is about to start,
+                  stopping on loops and after a million cycles only without a cycle
+break <pc>        break before running the instruction at index pc
+break x<op><n>    break before running an instruction while X compares to n, with op one of
+                  ==, !=, <, <=, >, >=
+delete            remove every breakpoint
+breakpoints       list breakpoints
+regs              print the registers
+crt               show the CRT drawn so far
+help              show this help
+quit              exit
+";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Breakpoint {
+    /// Before the instruction at this index.
+    Instruction(usize),
+    /// Before any instruction while X compares to a value.
+    X(Comparison, isize),
+}
+
+/// What to do after a command.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Reply {
+    Output(String),
+    Quit,
+}
+
+/// Runs commands against a program, drawing on a 40 pixels wide CRT.
+pub struct Debugger {
+    cpu: Cpu<Crt>,
+    breakpoints: Vec<Breakpoint>,
+    /// Whether `continue` ran before.
+    continued: bool,
+}
+
+impl Comparison {
+    fn holds(&self, a: isize, b: isize) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+        }
+    }
+}
+
+impl Breakpoint {
+    fn parse(s: &str) -> Result<Breakpoint, String> {
+        let invalid = || format!("Invalid breakpoint: {}", s);
+        let condition = match s.strip_prefix('x') {
+            Some(condition) => condition,
+            None => {
+                return s
+                    .parse()
+                    .map(Breakpoint::Instruction)
+                    .map_err(|_| invalid())
+            }
+        };
+        let operators = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+        let (value, comparison) = operators
+            .iter()
+            .find_map(|(operator, comparison)| {
+                condition
+                    .strip_prefix(operator)
+                    .map(|value| (value, *comparison))
+            })
+            .ok_or_else(invalid)?;
+        let value = value.parse().map_err(|_| invalid())?;
+        Ok(Breakpoint::X(comparison, value))
+    }
+
+    fn hit(&self, pc: usize, x: isize) -> bool {
+        match self {
+            Breakpoint::Instruction(index) => pc == *index,
+            Breakpoint::X(comparison, value) => comparison.holds(x, *value),
+        }
+    }
+
+    /// Index of the first breakpoint hit before running the CPU's next instruction.
+    fn first_hit(breakpoints: &[Breakpoint], cpu: &Cpu<Crt>) -> Option<usize> {
+        breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.hit(cpu.pc(), cpu.registers().x))
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+        write!(f, "{}", operator)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Instruction(index) => write!(f, "{}", index),
+            Breakpoint::X(comparison, value) => write!(f, "x{}{}", comparison, value),
+        }
+    }
+}
+
+impl Debugger {
+    pub fn new(instructions: Vec<Instruction>) -> Debugger {
+        Debugger {
+            cpu: Cpu::new(instructions).with_observer(Crt::new(40)),
+            breakpoints: Vec::new(),
+            continued: false,
+        }
+    }
+
+    /// Runs a command line, replying with its output or an error message.
+    pub fn execute(&mut self, line: &str) -> Result<Reply, String> {
+        let mut parts = line.split_whitespace();
+        let command = match parts.next() {
+            Some(command) => command,
+            None => return Ok(Reply::Output(String::new())),
+        };
+        let argument = parts.next();
+        if let Some(extra) = parts.next() {
+            return Err(format!("Unexpected argument: {}", extra));
+        }
+        let no_argument = || match argument {
+            Some(argument) => Err(format!("Unexpected argument: {}", argument)),
+            None => Ok(()),
+        };
+        let count = || match argument {
+            Some(count) => count
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid count: {}", count)),
+            None => Ok(1),
+        };
+
+        let output = match command {
+            "step" | "s" => {
+                for _ in 0..count()? {
+                    self.cpu.step_instruction();
+                }
+                self.location()
+            }
+            "tick" | "t" => {
+                for _ in 0..count()? {
+                    self.cpu.step();
+                }
+                self.location()
+            }
+            "continue" | "c" => self.resume(argument)?,
+            "break" | "b" => {
+                let breakpoint = Breakpoint::parse(argument.ok_or("Missing breakpoint")?)?;
+                self.breakpoints.push(breakpoint);
+                format!("Breakpoint {} at {}\n", self.breakpoints.len(), breakpoint)
+            }
+            "delete" | "d" => {
+                no_argument()?;
+                self.breakpoints.clear();
+                "Deleted every breakpoint\n".to_string()
+            }
+            "breakpoints" => {
+                no_argument()?;
+                self.breakpoints
+                    .iter()
+                    .enumerate()
+                    .map(|(i, breakpoint)| format!("{}: {}\n", i + 1, breakpoint))
+                    .collect()
+            }
+            "regs" | "r" => {
+                no_argument()?;
+                let registers = self.cpu.registers();
+                format!(
+                    "cycle={} pc={} x={} y={}\n",
+                    self.cpu.cycle(),
+                    self.cpu.pc(),
+                    registers.x,
+                    registers.y
+                )
+            }
+            "crt" => {
+                no_argument()?;
+                let mut crt = self.cpu.observer().to_string();
+                if !crt.ends_with('\n') {
+                    crt.push('\n');
+                }
+                crt
+            }
+            "help" | "h" => HELP.to_string(),
+            "quit" | "q" => {
+                no_argument()?;
+                return Ok(Reply::Quit);
+            }
+            command => return Err(format!("Unknown command: {}", command)),
+        };
+        Ok(Reply::Output(output))
+    }
+
+    fn resume(&mut self, cycle: Option<&str>) -> Result<String, String> {
+        let target = match cycle {
+            Some(cycle) => Some(
+                cycle
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid cycle: {}", cycle))?,
+            ),
+            None => None,
+        };
+        match target {
+            Some(target) if target < self.cpu.cycle() + 1 => {
+                return Err(format!("Cycle {} has already started", target))
+            }
+            Some(target) if target == self.cpu.cycle() + 1 => return Ok(self.location()),
+            _ => {}
+        }
+
+        let start = self.cpu.cycle();
+        let limit = target.unwrap_or(start + 1 + CONTINUE_CYCLES);
+        let continued = std::mem::replace(&mut self.continued, true);
+        // Whether the instruction in progress, or the next one, runs during the limit cycle.
+        let reaches = |cpu: &Cpu<Crt>| {
+            let instruction = cpu.instructions()[cpu.pc()];
+            cpu.cycle() + instruction.cycles() - cpu.elapsed() >= limit
+        };
+
+        // `run_to` never stops before the instruction it starts with, so check the first one's
+        // breakpoints here, unless the last `continue` already stopped on them.
+        let breakpoints = &self.breakpoints;
+        let mut hit = None;
+        if !self.cpu.halted() && start == 0 && !continued {
+            hit = Breakpoint::first_hit(breakpoints, &self.cpu);
+        }
+        let mut stuck = None;
+        let exit = if self.cpu.halted() || hit.is_some() || reaches(&self.cpu) {
+            None
+        } else {
+            loop {
+                let exit = self.cpu.run_to(|cpu| {
+                    hit = Breakpoint::first_hit(breakpoints, cpu);
+                    hit.is_some() || reaches(cpu)
+                });
+                // A loop still gets to the target, so only gets reported on the way.
+                match exit {
+                    Some(Exit::Loop { .. }) if target.is_some() => stuck = stuck.or(exit),
+                    exit => break exit,
+                }
+            }
+        };
+        // The limit can be in the middle of an instruction.
+        if exit.is_none() && hit.is_none() {
+            self.cpu.run_until(limit - 1);
+        }
+
+        let mut output = match (stuck.or(exit), hit) {
+            (Some(Exit::Loop { start, length }), _) => format!(
+                "Stuck in a loop of {} cycles since cycle {}\n",
+                length, start
+            ),
+            (_, Some(i)) => format!("Breakpoint {} at {}\n", i + 1, self.breakpoints[i]),
+            (None, None) if target.is_none() && !self.cpu.halted() => {
+                format!("Still running after {} cycles\n", self.cpu.cycle() - start)
+            }
+            _ => String::new(),
+        };
+        output += self.location().as_str();
+        Ok(output)
+    }
+
+    /// Where the program is at, as the next cycle and the instruction running during it.
+    fn location(&self) -> String {
+        if self.cpu.halted() {
+            return format!("Halted after {} cycles\n", self.cpu.cycle());
+        }
+        format!(
+            "cycle {}, x={}: {}  {}\n",
+            self.cpu.cycle() + 1,
+            self.cpu.registers().x,
+            self.cpu.pc(),
+            self.cpu.instructions()[self.cpu.pc()]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, parse_instructions};
+    use test_case::test_case;
+
+    fn output(debugger: &mut Debugger, line: &str) -> String {
+        match debugger.execute(line) {
+            Ok(Reply::Output(output)) => output,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn session() {
+        let mut debugger = Debugger::new(parse_instructions("assets/example.txt").unwrap());
+        assert_eq!(
+            output(&mut debugger, "step"),
+            "cycle 3, x=16: 1  addx -11\n"
+        );
+        assert_eq!(
+            output(&mut debugger, "tick"),
+            "cycle 4, x=16: 1  addx -11\n"
+        );
+        assert_eq!(
+            output(&mut debugger, "c 20"),
+            "cycle 20, x=21: 10  addx -1\n"
+        );
+        assert_eq!(output(&mut debugger, "regs"), "cycle=19 pc=10 x=21 y=0\n");
+        assert_eq!(output(&mut debugger, "crt"), "##..##..##..##..##.\n");
+
+        assert_eq!(output(&mut debugger, "break 12"), "Breakpoint 1 at 12\n");
+        assert_eq!(
+            output(&mut debugger, "break x<=2"),
+            "Breakpoint 2 at x<=2\n"
+        );
+        assert_eq!(
+            output(&mut debugger, "continue"),
+            "Breakpoint 1 at 12\ncycle 24, x=25: 12  addx -1\n"
+        );
+        assert_eq!(
+            output(&mut debugger, "c"),
+            "Breakpoint 2 at x<=2\ncycle 40, x=1: 20  addx 1\n"
+        );
+        assert_eq!(output(&mut debugger, "breakpoints"), "1: 12\n2: x<=2\n");
+        assert_eq!(
+            output(&mut debugger, "delete"),
+            "Deleted every breakpoint\n"
+        );
+        assert_eq!(output(&mut debugger, "c"), "Halted after 240 cycles\n");
+        assert_eq!(debugger.execute("quit"), Ok(Reply::Quit));
+    }
+
+    #[test]
+    fn stuck() {
+        let mut debugger = Debugger::new(parse_instructions("assets/example_loop.txt").unwrap());
+        assert_eq!(
+            output(&mut debugger, "continue"),
+            "Stuck in a loop of 5 cycles since cycle 4\ncycle 11, x=3: 3  addy -1\n"
+        );
+    }
+
+    #[test_case("c 13", "cycle 13, x=3: 4  jmp -2\n", "cycle=12 pc=4 x=3 y=0\n")]
+    #[test_case("c 100", "cycle 100, x=3: 2  addy 1\n", "cycle=99 pc=2 x=3 y=0\n")]
+    fn stuck_until(line: &str, location: &str, registers: &str) {
+        let mut debugger = Debugger::new(parse_instructions("assets/example_loop.txt").unwrap());
+        assert_eq!(
+            output(&mut debugger, line),
+            format!("Stuck in a loop of 5 cycles since cycle 4\n{}", location)
+        );
+        assert_eq!(output(&mut debugger, "regs"), registers);
+    }
+
+    #[test_case(&["c 2"], "cycle 2, x=1: 0  addx 15\n")]
+    #[test_case(&["step", "c 4"], "cycle 4, x=16: 1  addx -11\n")]
+    #[test_case(&["tick", "c 3"], "cycle 3, x=16: 1  addx -11\n")]
+    fn continue_within_instruction(lines: &[&str], expected: &str) {
+        let mut debugger = Debugger::new(parse_instructions("assets/example.txt").unwrap());
+        let (last, lines) = lines.split_last().unwrap();
+        for line in lines {
+            output(&mut debugger, line);
+        }
+        assert_eq!(output(&mut debugger, last), expected);
+    }
+
+    #[test]
+    fn first_breakpoint() {
+        let mut debugger = Debugger::new(parse_instructions("assets/example.txt").unwrap());
+        output(&mut debugger, "break 0");
+        assert_eq!(
+            output(&mut debugger, "continue"),
+            "Breakpoint 1 at 0\ncycle 1, x=1: 0  addx 15\n"
+        );
+        assert_eq!(output(&mut debugger, "c"), "Halted after 240 cycles\n");
+    }
+
+    #[test]
+    fn still_running() {
+        let mut debugger = Debugger::new(assemble("addx 1\njmp -1\n").unwrap());
+        assert_eq!(
+            output(&mut debugger, "continue"),
+            "Still running after 1000000 cycles\ncycle 1000001, x=333334: 0  addx 1\n"
+        );
+    }
+
+    #[test_case("jump", "Unknown command: jump")]
+    #[test_case("step two", "Invalid count: two")]
+    #[test_case("break", "Missing breakpoint")]
+    #[test_case("break x=3", "Invalid breakpoint: x=3")]
+    #[test_case("break y>3", "Invalid breakpoint: y>3")]
+    #[test_case("regs now", "Unexpected argument: now")]
+    fn errors(line: &str, expected: &str) {
+        let mut debugger = Debugger::new(parse_instructions("assets/example.txt").unwrap());
+        assert_eq!(debugger.execute(line), Err(expected.to_string()));
+    }
+}
@@ -0,0 +1,201 @@
+use super::Registers;
+use nom::bytes::complete::tag;
+use nom::character::complete::{char, digit1, one_of};
+use nom::combinator::{map_res, opt};
+use nom::error::{Error, ErrorKind};
+use nom::sequence::preceded;
+use nom::IResult;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Opcode {
+    Noop,
+    Addx,
+    Addy,
+    Mulx,
+    Jmp,
+    Jz,
+    Jnz,
+    Halt,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    /// The instruction's value, 0 for instructions without one.
+    pub operand: isize,
+}
+
+/// What runs after an instruction.
+pub enum Flow {
+    Next,
+    /// Jumps by an offset relative to the instruction.
+    Jump(isize),
+    Halt,
+}
+
+pub struct Spec {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub has_operand: bool,
+    pub cycles: usize,
+    pub execute: fn(&mut Registers, isize) -> Flow,
+}
+
+/// Every instruction, driving parsing, timing and execution.
+pub const ISA: [Spec; 8] = [
+    Spec {
+        opcode: Opcode::Noop,
+        mnemonic: "noop",
+        has_operand: false,
+        cycles: 1,
+        execute: |_, _| Flow::Next,
+    },
+    Spec {
+        opcode: Opcode::Addx,
+        mnemonic: "addx",
+        has_operand: true,
+        cycles: 2,
+        execute: |registers, value| {
+            registers.x = registers.x.wrapping_add(value);
+            Flow::Next
+        },
+    },
+    Spec {
+        opcode: Opcode::Addy,
+        mnemonic: "addy",
+        has_operand: true,
+        cycles: 2,
+        execute: |registers, value| {
+            registers.y = registers.y.wrapping_add(value);
+            Flow::Next
+        },
+    },
+    Spec {
+        opcode: Opcode::Mulx,
+        mnemonic: "mulx",
+        has_operand: true,
+        cycles: 4,
+        execute: |registers, value| {
+            registers.x = registers.x.wrapping_mul(value);
+            Flow::Next
+        },
+    },
+    Spec {
+        opcode: Opcode::Jmp,
+        mnemonic: "jmp",
+        has_operand: true,
+        cycles: 1,
+        execute: |_, offset| Flow::Jump(offset),
+    },
+    Spec {
+        opcode: Opcode::Jz,
+        mnemonic: "jz",
+        has_operand: true,
+        cycles: 2,
+        execute: |registers, offset| match registers.x {
+            0 => Flow::Jump(offset),
+            _ => Flow::Next,
+        },
+    },
+    Spec {
+        opcode: Opcode::Jnz,
+        mnemonic: "jnz",
+        has_operand: true,
+        cycles: 2,
+        execute: |registers, offset| match registers.x {
+            0 => Flow::Next,
+            _ => Flow::Jump(offset),
+        },
+    },
+    Spec {
+        opcode: Opcode::Halt,
+        mnemonic: "halt",
+        has_operand: false,
+        cycles: 1,
+        execute: |_, _| Flow::Halt,
+    },
+];
+
+impl Opcode {
+    pub fn spec(&self) -> &'static Spec {
+        ISA.iter().find(|spec| spec.opcode == *self).unwrap()
+    }
+}
+
+impl Instruction {
+    pub fn new(opcode: Opcode, operand: isize) -> Instruction {
+        Instruction { opcode, operand }
+    }
+
+    pub fn cycles(&self) -> usize {
+        self.opcode.spec().cycles
+    }
+
+    pub fn execute(&self, registers: &mut Registers) -> Flow {
+        (self.opcode.spec().execute)(registers, self.operand)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spec = self.opcode.spec();
+        if spec.has_operand {
+            write!(f, "{} {}", spec.mnemonic, self.operand)
+        } else {
+            write!(f, "{}", spec.mnemonic)
+        }
+    }
+}
+
+fn parse_value(str: &str) -> IResult<&str, isize> {
+    let (str, sign) = opt(one_of("+-"))(str)?;
+    let digit = map_res(digit1, |s: &str| s.parse::<isize>())(str);
+    match sign {
+        Some('-') => digit.map(|(str, value)| (str, -value)),
+        _ => digit,
+    }
+}
+
+pub fn parse_instruction(str: &str) -> IResult<&str, Instruction> {
+    for spec in ISA.iter() {
+        let rest = match tag::<&str, &str, Error<&str>>(spec.mnemonic)(str) {
+            Ok((rest, _)) => rest,
+            Err(_) => continue,
+        };
+        if !spec.has_operand {
+            return Ok((rest, Instruction::new(spec.opcode, 0)));
+        }
+        let (rest, operand) = preceded(char(' '), parse_value)(rest)?;
+        return Ok((rest, Instruction::new(spec.opcode, operand)));
+    }
+    Err(nom::Err::Error(Error::new(str, ErrorKind::Tag)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("noop", Instruction::new(Opcode::Noop, 0))]
+    #[test_case("addy -3", Instruction::new(Opcode::Addy, -3))]
+    #[test_case("jnz +2", Instruction::new(Opcode::Jnz, 2))]
+    #[test_case("halt", Instruction::new(Opcode::Halt, 0))]
+    fn parse(input: &str, expected: Instruction) {
+        assert_eq!(parse_instruction(input), Ok(("", expected)));
+    }
+
+    #[test_case("mulx")]
+    #[test_case("jmpx 1")]
+    #[test_case("subx 1")]
+    fn parse_error(input: &str) {
+        assert!(parse_instruction(input).is_err());
+    }
+
+    #[test]
+    fn table() {
+        for spec in ISA.iter() {
+            assert_eq!(spec.opcode.spec().mnemonic, spec.mnemonic);
+        }
+    }
+}
@@ -1,119 +1,60 @@
-use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::one_of;
-use nom::character::complete::{digit1, newline};
-use nom::combinator::{all_consuming, opt};
-use nom::combinator::{map, map_res};
-use nom::multi::separated_list1;
-use nom::sequence::{preceded, terminated};
-use nom::{Finish, IResult};
 use std::fs;
 
-#[derive(Debug)]
-struct Noop;
-
-#[derive(Debug)]
-struct Addx(isize);
-
-#[derive(Debug)]
-enum Instruction {
-    Noop(Noop),
-    Addx(Addx),
+mod asm;
+mod cpu;
+mod crt;
+mod debugger;
+mod isa;
+
+pub use asm::{annotate, assemble, decode, disassemble, encode, Error};
+pub use cpu::{Cpu, Exit, Observer, Registers, State};
+pub use crt::Crt;
+pub use debugger::{Breakpoint, Comparison, Debugger, Reply};
+pub use isa::{Flow, Instruction, Opcode, Spec, ISA};
+
+/// Sums the signal strength, the cycle number times X, during the given cycles.
+pub struct SignalStrengths {
+    cycles: Vec<usize>,
+    pub sum: isize,
 }
 
-struct Program {
-    instructions: Vec<Instruction>,
-    x: Vec<isize>,
-}
-
-impl Program {
-    fn new(instructions: Vec<Instruction>) -> Program {
-        Program {
-            instructions,
-            x: Vec::new(),
-        }
-    }
-
-    fn run(&mut self) {
-        let mut next = 1;
-        for instruction in self.instructions.iter() {
-            match instruction {
-                Instruction::Noop(_) => self.x.push(next),
-                Instruction::Addx(addx) => {
-                    self.x.push(next);
-                    self.x.push(next);
-                    next += addx.0;
-                }
-            }
-        }
+impl SignalStrengths {
+    pub fn new(cycles: Vec<usize>) -> SignalStrengths {
+        SignalStrengths { cycles, sum: 0 }
     }
 }
 
-fn parse_instructions(filename: &str) -> Vec<Instruction> {
-    fn parse_noop(i: &str) -> IResult<&str, Noop> {
-        map(tag("noop"), |_| Noop)(i)
-    }
-
-    fn parse_addx(str: &str) -> IResult<&str, Addx> {
-        map(preceded(tag("addx "), parse_value), Addx)(str)
-    }
-
-    fn parse_value(str: &str) -> IResult<&str, isize> {
-        let (str, sign) = opt(one_of("+-"))(str)?;
-        let digit = map_res(digit1, |s: &str| s.parse::<isize>())(str);
-        match sign {
-            Some('-') => digit.map(|(str, value)| (str, -value)),
-            _ => digit,
+impl Observer for SignalStrengths {
+    fn on_cycle(&mut self, state: &State) {
+        if self.cycles.contains(&state.cycle) {
+            self.sum += state.cycle as isize * state.registers.x;
         }
     }
+}
 
-    fn parse_instruction(str: &str) -> IResult<&str, Instruction> {
-        alt((
-            map(parse_noop, Instruction::Noop),
-            map(parse_addx, Instruction::Addx),
-        ))(str)
-    }
-
-    fn parse_instructions(input: &str) -> IResult<&str, Vec<Instruction>> {
-        terminated(separated_list1(newline, parse_instruction), newline)(input)
-    }
-
-    let input = fs::read_to_string(filename).unwrap();
-    let (_, instructions) = all_consuming(parse_instructions)(input.as_str())
-        .finish()
-        .unwrap();
-    instructions
+pub fn parse_instructions(filename: &str) -> Result<Vec<Instruction>, Error> {
+    assemble(&fs::read_to_string(filename)?)
 }
 
 pub fn sum_signal_strengths(filename: &str, cycles: Vec<usize>) -> isize {
-    let mut program = Program::new(parse_instructions(filename));
-    program.run();
-    program
-        .x
-        .iter()
-        .enumerate()
-        .map(|(i, x)| (i + 1, x))
-        .filter(|(i, _)| cycles.contains(i))
-        .map(|(i, x)| i as isize * x)
-        .sum()
+    let mut cpu =
+        Cpu::new(parse_instructions(filename).unwrap()).with_observer(SignalStrengths::new(cycles));
+    cpu.run();
+    cpu.observer().sum
 }
 
 pub fn draw_crt(filename: &str, line_width: usize) -> String {
-    let mut program = Program::new(parse_instructions(filename));
-    program.run();
-    let x = program.x;
-    x.iter()
-        .enumerate()
-        .fold("".to_string(), |mut acc, (i, _)| {
-            let max_row = line_width - 1;
-            let pixel = (i % line_width) as isize;
-            let sprite = x[i] - 1..=x[i] + 1;
-            acc += if sprite.contains(&pixel) { "#" } else { "." };
-            if i % line_width == max_row {
-                acc += "\n"
-            }
-            acc
-        })
+    let mut cpu =
+        Cpu::new(parse_instructions(filename).unwrap()).with_observer(Crt::new(line_width));
+    cpu.run();
+    cpu.observer().to_string()
+}
+
+/// Reads the letters the program draws on a 40 pixels wide CRT.
+pub fn read_crt(filename: &str) -> String {
+    let mut cpu = Cpu::new(parse_instructions(filename).unwrap()).with_observer(Crt::new(40));
+    cpu.run();
+    cpu.observer().read()
 }
 
 #[cfg(test)]
@@ -1,4 +1,4 @@
-use day10::{draw_crt, sum_signal_strengths};
+use day10::{draw_crt, read_crt, sum_signal_strengths};
 
 fn main() {
     println!(
@@ -6,5 +6,9 @@ fn main() {
         sum_signal_strengths("day10/assets/input.txt", vec![20, 60, 100, 140, 180, 220])
     );
 
-    println!("Part two:\n{}", draw_crt("day10/assets/input.txt", 40));
+    println!(
+        "Part two: {}\n{}",
+        read_crt("day10/assets/input.txt"),
+        draw_crt("day10/assets/input.txt", 40)
+    );
 }
@@ -55,6 +55,8 @@ impl Monkey {
     fn inspect(&mut self, worry_divisor: u64) -> Option<(MonkeyIndex, WorryLevel)> {
         let worry_level = self.items.pop_front()?;
         let new_worry_level = (self.operation_fn)(worry_level) / worry_divisor;
+        // `is_multiple_of` needs Rust 1.87.
+        #[allow(clippy::manual_is_multiple_of)]
         let index = if new_worry_level % self.test_divisor == 0 {
             self.test_outcome.0
         } else {
@@ -194,7 +196,7 @@ pub fn monkey_business_after_rounds(filename: &str, rounds: usize, worry_divisor
     game.run_rounds(rounds);
     let mut inspections = game.inspections.clone();
     inspections.sort();
-    return inspections.iter().rev().take(2).product();
+    inspections.iter().rev().take(2).product()
 }
 
 #[cfg(test)]
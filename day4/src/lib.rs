@@ -29,15 +29,15 @@ pub fn count_subranges(
 }
 
 pub fn fully_contains<T: Ord>(first: RangeInclusive<T>, second: RangeInclusive<T>) -> bool {
-    return first.start() <= second.start() && first.end() >= second.end()
-        || second.start() <= first.start() && second.end() >= first.end();
+    first.start() <= second.start() && first.end() >= second.end()
+        || second.start() <= first.start() && second.end() >= first.end()
 }
 
 pub fn partially_contains<T: Ord>(first: RangeInclusive<T>, second: RangeInclusive<T>) -> bool {
-    return first.start() <= second.start() && second.start() <= first.end()
+    first.start() <= second.start() && second.start() <= first.end()
         || first.start() <= second.end() && second.end() <= first.end()
         || second.start() <= first.start() && first.start() <= second.end()
-        || second.start() <= first.end() && first.end() <= second.end();
+        || second.start() <= first.end() && first.end() <= second.end()
 }
 
 #[cfg(test)]
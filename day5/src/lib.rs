@@ -1,23 +1,69 @@
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::vec::IntoIter;
 
-struct Stacks(HashMap<usize, Stack>);
+#[derive(Clone, PartialEq, Eq)]
+pub struct Stacks(HashMap<usize, Stack>);
 
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 struct Stack {
     line_index: usize,
     crates: Vec<char>,
 }
 
 #[derive(Debug)]
-struct Move {
+pub struct Move {
     count: usize,
     from: usize,
     to: usize,
 }
 
+/// A stack whose crates differ between two states, listed bottom to top.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Change {
+    pub number: usize,
+    pub before: Vec<char>,
+    pub after: Vec<char>,
+}
+
+/// Replays the moves one at a time, keeping the stacks as of the current step.
+pub struct Simulation<C: Crane> {
+    initial: Stacks,
+    stacks: Stacks,
+    moves: Vec<Move>,
+    crane: C,
+    step: usize,
+}
+
+pub trait Crane {
+    /// Takes the crates lifted off a stack (bottom to top) and returns them in the order they
+    /// land on the destination stack (bottom to top), counting the lifts it took.
+    fn transfer(&mut self, crates: Vec<char>) -> Vec<char>;
+
+    fn lifts(&self) -> usize;
+
+    fn reset(&mut self);
+}
+
+/// Moves crates one at a time.
+#[derive(Default)]
+pub struct CrateMover9000 {
+    lifts: usize,
+}
+
+/// Moves all crates at once.
+#[derive(Default)]
+pub struct CrateMover9001 {
+    lifts: usize,
+}
+
+/// Moves at most `capacity` crates per lift, keeping the order of each lifted group.
+pub struct LimitedCrane {
+    capacity: usize,
+    lifts: usize,
+}
+
 impl Stacks {
     fn new(lines: Vec<String>) -> Self {
         let mut stacks_lines = lines.iter().rev();
@@ -34,7 +80,6 @@ impl Stacks {
                 };
                 (number, stack)
             })
-            .into_iter()
             .collect();
 
         stacks_lines.for_each(|stack_line| {
@@ -50,8 +95,68 @@ impl Stacks {
         Stacks(stacks)
     }
 
-    fn numbers(self) -> IntoIter<(usize, Stack)> {
-        self.0.into_iter().sorted_by_key(|el| el.0)
+    fn numbers(&self) -> impl Iterator<Item = (&usize, &Stack)> {
+        self.0.iter().sorted_by_key(|(number, _)| **number)
+    }
+
+    fn tops(&self) -> String {
+        self.numbers()
+            .filter_map(|(_, stack)| stack.crates.last())
+            .collect()
+    }
+
+    pub fn diff(&self, other: &Stacks) -> Vec<Change> {
+        self.numbers()
+            .filter_map(|(number, stack)| {
+                let after = &other.0.get(number)?.crates;
+                if stack.crates == *after {
+                    return None;
+                }
+                Some(Change {
+                    number: *number,
+                    before: stack.crates.clone(),
+                    after: after.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self
+            .0
+            .values()
+            .map(|stack| stack.crates.len())
+            .max()
+            .unwrap_or(0);
+        for level in (0..height).rev() {
+            let line = self
+                .numbers()
+                .map(|(_, stack)| match stack.crates.get(level) {
+                    Some(c) => format!("[{}]", c),
+                    None => "   ".to_string(),
+                })
+                .join(" ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        let numbers = self
+            .numbers()
+            .map(|(number, _)| format!(" {} ", number))
+            .join(" ");
+        write!(f, "{}", numbers.trim_end())
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.number,
+            self.before.iter().collect::<String>(),
+            self.after.iter().collect::<String>()
+        )
     }
 }
 
@@ -68,24 +173,162 @@ impl Move {
         Move { count, from, to }
     }
 
-    fn apply(&mut self, stacks: &mut Stacks, move_multiple: bool) {
+    fn apply(&self, stacks: &mut Stacks, crane: &mut impl Crane) {
         let from = stacks.0.get_mut(&self.from).unwrap();
         let to_move = from.crates.split_off(from.crates.len() - self.count);
         let to = stacks.0.get_mut(&self.to).unwrap();
-        if move_multiple {
-            to.crates.extend(to_move.iter());
+        to.crates.extend(crane.transfer(to_move));
+    }
+}
+
+impl Crane for CrateMover9000 {
+    fn transfer(&mut self, crates: Vec<char>) -> Vec<char> {
+        self.lifts += crates.len();
+        crates.into_iter().rev().collect()
+    }
+
+    fn lifts(&self) -> usize {
+        self.lifts
+    }
+
+    fn reset(&mut self) {
+        self.lifts = 0;
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn transfer(&mut self, crates: Vec<char>) -> Vec<char> {
+        if !crates.is_empty() {
+            self.lifts += 1;
+        }
+        crates
+    }
+
+    fn lifts(&self) -> usize {
+        self.lifts
+    }
+
+    fn reset(&mut self) {
+        self.lifts = 0;
+    }
+}
+
+impl<C: Crane + ?Sized> Crane for &mut C {
+    fn transfer(&mut self, crates: Vec<char>) -> Vec<char> {
+        (**self).transfer(crates)
+    }
+
+    fn lifts(&self) -> usize {
+        (**self).lifts()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+impl LimitedCrane {
+    pub fn new(capacity: usize) -> LimitedCrane {
+        assert!(capacity > 0, "Capacity must be positive");
+        LimitedCrane { capacity, lifts: 0 }
+    }
+}
+
+impl Crane for LimitedCrane {
+    fn transfer(&mut self, crates: Vec<char>) -> Vec<char> {
+        let groups = crates.rchunks(self.capacity);
+        self.lifts += groups.len();
+        groups.flatten().copied().collect()
+    }
+
+    fn lifts(&self) -> usize {
+        self.lifts
+    }
+
+    fn reset(&mut self) {
+        self.lifts = 0;
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+impl<C: Crane> Simulation<C> {
+    pub fn new(filename: String, crane: C) -> Simulation<C> {
+        let (stacks, moves) = parse(filename);
+        Simulation {
+            initial: stacks.clone(),
+            stacks,
+            moves,
+            crane,
+            step: 0,
+        }
+    }
+
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn steps(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn stacks(&self) -> &Stacks {
+        &self.stacks
+    }
+
+    pub fn crane(&self) -> &C {
+        &self.crane
+    }
+
+    pub fn next_move(&self) -> Option<&Move> {
+        self.moves.get(self.step)
+    }
+
+    /// Applies the next move, returning the stacks it changed.
+    pub fn forward(&mut self) -> Option<Vec<Change>> {
+        let before = self.stacks.clone();
+        if self.apply_next() {
+            Some(before.diff(&self.stacks))
         } else {
-            to.crates.extend(to_move.iter().rev());
+            None
+        }
+    }
+
+    /// Moves to the state right after `step` moves, replaying from the start if going back.
+    pub fn seek(&mut self, step: usize) {
+        assert!(step <= self.steps(), "Step out of range: {}", step);
+        if step < self.step {
+            self.stacks = self.initial.clone();
+            self.crane.reset();
+            self.step = 0;
+        }
+        while self.step < step {
+            self.apply_next();
+        }
+    }
+
+    fn apply_next(&mut self) -> bool {
+        match self.moves.get(self.step) {
+            Some(mov) => {
+                mov.apply(&mut self.stacks, &mut self.crane);
+                self.step += 1;
+                true
+            }
+            None => false,
         }
     }
 }
 
-pub fn crates_on_top(filename: String, move_multiple: bool) -> String {
+fn parse(filename: String) -> (Stacks, Vec<Move>) {
     let input = fs::read_to_string(filename).unwrap();
     let (stacks_input, moves_input) = input.split_once("\n\n").unwrap();
 
     // Parse stacks.
-    let mut stacks = Stacks::new(stacks_input.lines().map(|l| l.to_string()).collect());
+    let stacks = Stacks::new(stacks_input.lines().map(|l| l.to_string()).collect());
 
     // Parse moves.
     let moves = moves_input
@@ -93,14 +336,13 @@ pub fn crates_on_top(filename: String, move_multiple: bool) -> String {
         .map(|line| Move::new(line.to_string()))
         .collect::<Vec<Move>>();
 
-    // Apply moves.
-    for mut mov in moves {
-        mov.apply(&mut stacks, move_multiple);
-    }
+    (stacks, moves)
+}
 
-    stacks.numbers().fold(String::new(), |acc, (_, stack)| {
-        acc + stack.crates.last().unwrap().to_string().as_str()
-    })
+pub fn crates_on_top(filename: String, crane: &mut impl Crane) -> String {
+    let mut simulation = Simulation::new(filename, crane);
+    simulation.seek(simulation.steps());
+    simulation.stacks().tops()
 }
 
 #[cfg(test)]
@@ -109,13 +351,78 @@ mod tests {
 
     #[test]
     fn part1() {
-        let result = crates_on_top("assets/example.txt".to_string(), false);
+        let mut crane = CrateMover9000::default();
+        let result = crates_on_top("assets/example.txt".to_string(), &mut crane);
         assert_eq!(result, "CMZ");
+        assert_eq!(crane.lifts(), 7);
     }
 
     #[test]
     fn part2() {
-        let result = crates_on_top("assets/example.txt".to_string(), true);
+        let mut crane = CrateMover9001::default();
+        let result = crates_on_top("assets/example.txt".to_string(), &mut crane);
         assert_eq!(result, "MCD");
+        assert_eq!(crane.lifts(), 4);
+    }
+
+    #[test]
+    fn stacks_fmt() {
+        let input = fs::read_to_string("assets/example.txt").unwrap();
+        let (stacks_input, _) = input.split_once("\n\n").unwrap();
+        let (stacks, _) = parse("assets/example.txt".to_string());
+        assert_eq!(stacks.to_string(), stacks_input);
+    }
+
+    #[test]
+    fn simulation() {
+        let mut simulation =
+            Simulation::new("assets/example.txt".to_string(), CrateMover9000::default());
+        assert_eq!(
+            simulation.next_move().unwrap().to_string(),
+            "move 1 from 2 to 1"
+        );
+        assert_eq!(
+            simulation.forward().unwrap(),
+            vec![
+                Change {
+                    number: 1,
+                    before: vec!['Z', 'N'],
+                    after: vec!['Z', 'N', 'D'],
+                },
+                Change {
+                    number: 2,
+                    before: vec!['M', 'C', 'D'],
+                    after: vec!['M', 'C'],
+                },
+            ]
+        );
+
+        simulation.seek(3);
+        assert_eq!(
+            simulation.stacks().to_string(),
+            "
+        [Z]
+        [N]
+[M]     [D]
+[C]     [P]
+ 1   2   3
+            "
+            .trim_start_matches('\n')
+            .trim_end()
+        );
+
+        simulation.seek(1);
+        assert_eq!(simulation.stacks().tops(), "DCP");
+        assert_eq!(simulation.crane().lifts(), 1);
+        simulation.seek(simulation.steps());
+        assert_eq!(simulation.forward(), None);
+    }
+
+    #[test]
+    fn limited_crane() {
+        let mut crane = LimitedCrane::new(2);
+        let result = crates_on_top("assets/example.txt".to_string(), &mut crane);
+        assert_eq!(result, "MCZ");
+        assert_eq!(crane.lifts(), 5);
     }
 }
@@ -1,13 +1,19 @@
-use day5::crates_on_top;
+use day5::{crates_on_top, CrateMover9000, CrateMover9001};
 
 fn main() {
     println!(
         "Part one: {}",
-        crates_on_top("day5/assets/input.txt".to_string(), false)
+        crates_on_top(
+            "day5/assets/input.txt".to_string(),
+            &mut CrateMover9000::default()
+        )
     );
 
     println!(
         "Part two: {}",
-        crates_on_top("day5/assets/input.txt".to_string(), true)
+        crates_on_top(
+            "day5/assets/input.txt".to_string(),
+            &mut CrateMover9001::default()
+        )
     );
 }
@@ -1,28 +1,324 @@
-use std::fs;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufReader, Read};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub fn index_of_marker(filename: String, window_size: usize) -> Option<usize> {
-    let input = fs::read_to_string(filename).unwrap();
+/// Something markers can be made of, along with how to count its occurrences in a window.
+pub trait Symbol: Clone + Eq + Hash + fmt::Debug {
+    type Counts: Default;
+
+    fn count<'a>(counts: &'a mut Self::Counts, symbol: &Self) -> &'a mut usize;
+}
+
+pub struct ByteCounts([usize; 256]);
+
+impl Default for ByteCounts {
+    fn default() -> Self {
+        ByteCounts([0; 256])
+    }
+}
+
+impl Symbol for u8 {
+    type Counts = ByteCounts;
+
+    fn count<'a>(counts: &'a mut ByteCounts, symbol: &u8) -> &'a mut usize {
+        &mut counts.0[*symbol as usize]
+    }
+}
+
+impl Symbol for char {
+    type Counts = HashMap<char, usize>;
+
+    fn count<'a>(counts: &'a mut Self::Counts, symbol: &char) -> &'a mut usize {
+        counts.entry(*symbol).or_insert(0)
+    }
+}
+
+/// Grapheme clusters, as yielded by `UnicodeSegmentation::graphemes`.
+impl<'s> Symbol for &'s str {
+    type Counts = HashMap<&'s str, usize>;
+
+    fn count<'a>(counts: &'a mut Self::Counts, symbol: &&'s str) -> &'a mut usize {
+        counts.entry(*symbol).or_insert(0)
+    }
+}
+
+/// The unit a datastream is split into, which is also the unit of the reported offsets.
+#[derive(Clone, Copy, Debug)]
+pub enum Unit {
+    Bytes,
+    Chars,
+    Graphemes,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    OutOfAlphabet { offset: usize, symbol: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::OutOfAlphabet { offset, symbol } => {
+                write!(
+                    f,
+                    "Symbol {} at offset {} is not in the alphabet",
+                    symbol, offset
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Tracks the last `window_size` symbols of a stream and whether they are all distinct.
+pub struct MarkerDetector<T: Symbol> {
+    window: VecDeque<T>,
+    window_size: usize,
+    counts: T::Counts,
+    duplicates: usize,
+    position: usize,
+}
+
+impl<T: Symbol> MarkerDetector<T> {
+    pub fn new(window_size: usize) -> MarkerDetector<T> {
+        assert!(window_size > 0, "Window size must be positive");
+        MarkerDetector {
+            window: VecDeque::with_capacity(window_size),
+            window_size,
+            counts: T::Counts::default(),
+            duplicates: 0,
+            position: 0,
+        }
+    }
+
+    /// Number of symbols pushed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Pushes the next symbol, returning whether the window ending at it is a marker.
+    pub fn push(&mut self, symbol: T) -> bool {
+        // Evict the symbol leaving the window.
+        if self.window.len() == self.window_size {
+            let old = self.window.pop_front().unwrap();
+            let count = T::count(&mut self.counts, &old);
+            *count -= 1;
+            if *count == 1 {
+                self.duplicates -= 1;
+            }
+        }
+
+        let count = T::count(&mut self.counts, &symbol);
+        *count += 1;
+        if *count == 2 {
+            self.duplicates += 1;
+        }
+        self.window.push_back(symbol);
+        self.position += 1;
+
+        self.window.len() == self.window_size && self.duplicates == 0
+    }
+}
 
-    // Use the chars method to iterate over the characters in the string
-    for (i, _) in input.chars().enumerate() {
-        if i + window_size >= input.len() {
-            break;
+/// Iterates over the end offset of every window of `window_size` distinct symbols.
+pub struct Markers<T: Symbol, I: Iterator<Item = io::Result<T>>> {
+    symbols: I,
+    detector: MarkerDetector<T>,
+    alphabet: Option<HashSet<T>>,
+    failed: bool,
+}
+
+impl<R: Read> Markers<u8, io::Bytes<BufReader<R>>> {
+    pub fn new(reader: R, window_size: usize) -> Self {
+        Markers::from_symbols(BufReader::new(reader).bytes(), window_size)
+    }
+}
+
+impl<T: Symbol, I: Iterator<Item = io::Result<T>>> Markers<T, I> {
+    pub fn from_symbols(symbols: I, window_size: usize) -> Self {
+        Markers {
+            symbols,
+            detector: MarkerDetector::new(window_size),
+            alphabet: None,
+            failed: false,
         }
+    }
 
-        let window = &input[i..i + window_size];
+    /// Rejects symbols outside of `alphabet`, stopping at the first one.
+    pub fn with_alphabet(mut self, alphabet: impl IntoIterator<Item = T>) -> Self {
+        self.alphabet = Some(alphabet.into_iter().collect());
+        self
+    }
+}
+
+impl<T: Symbol, I: Iterator<Item = io::Result<T>>> Iterator for Markers<T, I> {
+    type Item = Result<usize, Error>;
 
-        if window
-            .chars()
-            .enumerate()
-            .any(|(i, c)| window[i + 1..].contains(c))
-        {
-            continue;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
         }
+        for symbol in self.symbols.by_ref() {
+            let symbol = match symbol {
+                Ok(symbol) => symbol,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(Error::Io(error)));
+                }
+            };
+            if let Some(alphabet) = &self.alphabet {
+                if !alphabet.contains(&symbol) {
+                    self.failed = true;
+                    return Some(Err(Error::OutOfAlphabet {
+                        offset: self.detector.position(),
+                        symbol: format!("{:?}", symbol),
+                    }));
+                }
+            }
+            if self.detector.push(symbol) {
+                return Some(Ok(self.detector.position()));
+            }
+        }
+        None
+    }
+}
 
-        return Some(window_size + i);
+/// A packet in a datastream, as byte offsets into it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Packet {
+    /// Offset right after the start-of-packet marker.
+    pub start: usize,
+    /// Offset right after the start-of-message marker, if the packet has one.
+    pub message: Option<usize>,
+    /// Offset where the next start-of-packet marker begins, or the end of the stream.
+    pub end: usize,
+}
+
+/// Splits a datastream into packets at start-of-packet markers, giving each packet the first
+/// start-of-message marker found while it lasts. Both kinds of markers are looked for over the
+/// whole stream, each search starting over after a marker of its kind, so markers of one kind
+/// never overlap and a start-of-packet marker always ends the current packet.
+pub struct Decoder<R: Read> {
+    bytes: io::Bytes<BufReader<R>>,
+    packet_window: usize,
+    message_window: usize,
+    packets: MarkerDetector<u8>,
+    messages: MarkerDetector<u8>,
+    pending: Option<Packet>,
+    offset: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R, packet_window: usize, message_window: usize) -> Decoder<R> {
+        Decoder {
+            bytes: BufReader::new(reader).bytes(),
+            packet_window,
+            message_window,
+            packets: MarkerDetector::new(packet_window),
+            messages: MarkerDetector::new(message_window),
+            pending: None,
+            offset: 0,
+        }
     }
+}
 
-    None
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = io::Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            let byte = match byte {
+                Ok(byte) => byte,
+                Err(error) => return Some(Err(error)),
+            };
+            self.offset += 1;
+            let packet_marker = self.packets.push(byte);
+            let message_marker = self.messages.push(byte);
+            let mut done = None;
+
+            // A start-of-packet marker ends the previous packet.
+            if packet_marker {
+                self.packets = MarkerDetector::new(self.packet_window);
+                let next = Packet {
+                    start: self.offset,
+                    message: None,
+                    end: self.offset,
+                };
+                done = self.pending.replace(next).map(|mut packet| {
+                    packet.end = self.offset - self.packet_window;
+                    // A start-of-message marker ending within the next marker is in no packet.
+                    packet.message = packet.message.filter(|message| *message <= packet.end);
+                    packet
+                });
+            }
+            if message_marker {
+                self.messages = MarkerDetector::new(self.message_window);
+                if let Some(packet) = &mut self.pending {
+                    packet.message.get_or_insert(self.offset);
+                }
+            }
+            if let Some(packet) = done {
+                return Some(Ok(packet));
+            }
+        }
+
+        self.pending.take().map(|mut packet| {
+            packet.end = self.offset;
+            Ok(packet)
+        })
+    }
+}
+
+pub fn find_marker(reader: impl Read, window_size: usize) -> Result<Option<usize>, Error> {
+    Markers::new(reader, window_size).next().transpose()
+}
+
+/// Finds the first marker in `input` split into `unit`s, optionally restricted to the symbols
+/// of `alphabet` split the same way. The offset is counted in `unit`s.
+pub fn find_marker_in(
+    input: &str,
+    window_size: usize,
+    unit: Unit,
+    alphabet: Option<&str>,
+) -> Result<Option<usize>, Error> {
+    fn first<T: Symbol>(
+        symbols: impl Iterator<Item = T>,
+        window_size: usize,
+        alphabet: Option<impl Iterator<Item = T>>,
+    ) -> Result<Option<usize>, Error> {
+        let mut markers = Markers::from_symbols(symbols.map(Ok), window_size);
+        if let Some(alphabet) = alphabet {
+            markers = markers.with_alphabet(alphabet);
+        }
+        markers.next().transpose()
+    }
+
+    match unit {
+        Unit::Bytes => first(input.bytes(), window_size, alphabet.map(str::bytes)),
+        Unit::Chars => first(input.chars(), window_size, alphabet.map(str::chars)),
+        Unit::Graphemes => first(
+            input.graphemes(true),
+            window_size,
+            alphabet.map(|alphabet| alphabet.graphemes(true)),
+        ),
+    }
+}
+
+pub fn index_of_marker(filename: String, window_size: usize) -> Option<usize> {
+    let file = File::open(filename).unwrap();
+    find_marker(file, window_size).unwrap()
 }
 
 #[cfg(test)]
@@ -49,4 +345,84 @@ mod tests {
         let result = index_of_marker(filename.to_string(), 14);
         assert_eq!(result, Some(index));
     }
+
+    #[test_case("abcd", 4, Some(4))]
+    #[test_case("aabbcdd", 3, Some(6))]
+    #[test_case("abcabc", 4, None)]
+    #[test_case("", 1, None)]
+    fn stream(input: &str, window_size: usize, expected: Option<usize>) {
+        let result = find_marker(input.as_bytes(), window_size).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test_case(Unit::Bytes, 3)]
+    #[test_case(Unit::Chars, 5)]
+    #[test_case(Unit::Graphemes, 4)]
+    fn units(unit: Unit, expected: usize) {
+        let result = find_marker_in("e\u{301}e\u{301}ax", 3, unit, None).unwrap();
+        assert_eq!(result, Some(expected));
+    }
+
+    #[test]
+    fn alphabet() {
+        let result = find_marker_in("abcxd", 4, Unit::Chars, Some("abcd"));
+        match result {
+            Err(Error::OutOfAlphabet { offset, symbol }) => {
+                assert_eq!(offset, 3);
+                assert_eq!(symbol, "'x'");
+            }
+            _ => panic!("Expected an out-of-alphabet error, got {:?}", result),
+        }
+        let result = find_marker_in("ab€d", 4, Unit::Chars, Some("abcd€")).unwrap();
+        assert_eq!(result, Some(4));
+    }
+
+    #[test]
+    fn all_markers() {
+        let markers = Markers::new("mjqjpqmgbljsphdztnvjfqwrcgsmlb".as_bytes(), 4)
+            .take(6)
+            .collect::<Result<Vec<usize>, Error>>()
+            .unwrap();
+        assert_eq!(markers, vec![7, 8, 9, 10, 11, 12]);
+    }
+
+    #[test_case(
+        "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+        4,
+        14,
+        vec![
+            (7, None, 7),
+            (11, None, 11),
+            (15, None, 15),
+            (19, Some(19), 19),
+            (23, None, 23),
+            (27, None, 30)
+        ]
+    )]
+    #[test_case(
+        "abcdaaaaefghaaaaijklaaaa",
+        4,
+        6,
+        vec![(4, None, 7), (11, None, 15), (19, None, 24)]
+    )]
+    #[test_case("abcdaaaaabcdefaaaa", 4, 6, vec![(4, None, 8), (12, Some(14), 18)])]
+    fn decoder(
+        stream: &str,
+        packet_window: usize,
+        message_window: usize,
+        expected: Vec<(usize, Option<usize>, usize)>,
+    ) {
+        let packets = Decoder::new(stream.as_bytes(), packet_window, message_window)
+            .collect::<io::Result<Vec<Packet>>>()
+            .unwrap();
+        let expected = expected
+            .into_iter()
+            .map(|(start, message, end)| Packet {
+                start,
+                message,
+                end,
+            })
+            .collect::<Vec<Packet>>();
+        assert_eq!(packets, expected);
+    }
 }
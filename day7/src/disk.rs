@@ -0,0 +1,140 @@
+use super::{is_valid_name, FileSystem};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl FileSystem {
+    /// Creates the tree under `root`, with every file as a sparse file of its recorded size. Fails
+    /// without writing anything when a name could lead outside `root`.
+    pub fn export(&self, root: &Path) -> io::Result<()> {
+        let mut invalid = None;
+        self.traverse(self.root(), &mut |id, _| {
+            if id != self.root() && invalid.is_none() && !is_valid_name(self.name(id)) {
+                invalid = Some(self.path(id));
+            }
+        });
+        if let Some(path) = invalid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: Invalid file name", path),
+            ));
+        }
+
+        let mut result = Ok(());
+        self.traverse(self.root(), &mut |id, _| {
+            if result.is_err() {
+                return;
+            }
+            let path = root.join(self.path(id).trim_start_matches('/'));
+            result = if self.is_dir(id) {
+                fs::create_dir_all(path)
+            } else {
+                File::create(path).and_then(|file| file.set_len(self.size(id) as u64))
+            };
+        });
+        result
+    }
+
+    /// Exports the tree under a new directory in the system's temporary directory.
+    pub fn export_to_temp_dir(&self) -> io::Result<PathBuf> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = env::temp_dir().join(format!("day7-{}-{}", std::process::id(), nanos));
+        fs::create_dir(&root)?;
+        self.export(&root)?;
+        Ok(root)
+    }
+}
+
+/// Walks the directory at `root` and lists it as a transcript of `cd` and `ls` commands, with
+/// entries sorted by name. Symbolic links are left out.
+pub fn transcript(root: &Path) -> io::Result<String> {
+    fn list(path: &Path, output: &mut String) -> io::Result<()> {
+        let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        let mut dirs = Vec::new();
+        *output += "$ ls\n";
+        for entry in entries {
+            let name = entry.file_name().into_string().map_err(|name| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{:?}: Not valid UTF-8", name),
+                )
+            })?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                *output += format!("dir {}\n", name).as_str();
+                dirs.push(name);
+            } else if metadata.is_file() {
+                *output += format!("{} {}\n", metadata.len(), name).as_str();
+            }
+        }
+
+        for name in dirs {
+            *output += format!("$ cd {}\n", name).as_str();
+            list(&path.join(name), output)?;
+            *output += "$ cd ..\n";
+        }
+        Ok(())
+    }
+
+    let mut output = "$ cd /\n".to_string();
+    list(root, &mut output)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{example, Kind};
+
+    #[test]
+    fn round_trip() {
+        let fs = example();
+        let root = fs.export_to_temp_dir().unwrap();
+        assert_eq!(fs::metadata(root.join("d/d.log")).unwrap().len(), 8033020);
+
+        let output = transcript(&root).unwrap();
+        let imported = FileSystem::from_output(output.clone()).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(imported.size(imported.root()), 48381165);
+
+        let mut expected = fs
+            .du_all(false)
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let mut actual = imported
+            .du_all(false)
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        let root = imported.export_to_temp_dir().unwrap();
+        let reimported = transcript(&root).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(reimported, output);
+    }
+
+    #[test]
+    fn export_outside_root() {
+        let root = env::temp_dir().join(format!("day7-outside-{}", std::process::id()));
+        for name in ["..", ".", "", "x/../.."] {
+            let mut fs = FileSystem::from_output("$ cd /\n$ ls\ndir a\n".to_string()).unwrap();
+            let a = fs.child(fs.root(), "a").unwrap();
+            fs.add_child(a, name.to_string(), Kind::File, 1);
+
+            let error = fs.export(&root).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            assert!(!root.exists());
+        }
+    }
+}
@@ -1,136 +1,196 @@
-use std::cell::RefCell;
+use std::fmt;
 use std::fs;
-use std::rc::Rc;
 
-struct Folder {
-    name: String,
-    parent: Option<Rc<RefCell<Node>>>,
-    children: Vec<Rc<RefCell<Node>>>,
+mod disk;
+mod planner;
+mod query;
+mod render;
+
+pub use disk::transcript;
+pub use planner::{Plan, Strategy};
+pub use query::{DepthStats, Entry};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Folder,
+    File,
 }
 
-struct File {
+struct Node {
     name: String,
-    parent: Rc<RefCell<Node>>,
+    kind: Kind,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
     size: usize,
 }
 
-enum Node {
-    Folder(Folder),
-    File(File),
+/// Arena of nodes, where the root is always the first one and children always come after their
+/// parent. Directory sizes are cached and refreshed by `update_sizes`.
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    NotADirectory(String),
+    NoSuchDirectory(String),
+    ConflictingKind(String),
+    ConflictingSize {
+        path: String,
+        recorded: usize,
+        listed: usize,
+    },
+    UnsupportedCommand(String),
+    InvalidListing(String),
 }
 
 struct Session {
-    root: Rc<RefCell<Node>>,
-    node: Rc<RefCell<Node>>,
+    fs: FileSystem,
+    node: NodeId,
 }
 
-impl Folder {
-    fn new(name: String, parent: Option<Rc<RefCell<Node>>>) -> Folder {
-        Folder {
+impl Node {
+    fn new(name: String, kind: Kind, parent: Option<NodeId>, size: usize) -> Node {
+        Node {
             name,
+            kind,
             parent,
             children: Vec::new(),
+            size,
         }
     }
+}
 
-    fn root() -> Folder {
-        Folder {
-            name: "/".to_string(),
-            parent: None,
-            children: Vec::new(),
+impl FileSystem {
+    fn new() -> FileSystem {
+        FileSystem {
+            nodes: vec![Node::new("/".to_string(), Kind::Folder, None, 0)],
         }
     }
-}
 
-impl File {
-    fn new(name: String, parent: Rc<RefCell<Node>>, size: usize) -> File {
-        File { name, parent, size }
+    pub fn from_output(output: String) -> Result<FileSystem, Error> {
+        Ok(Session::from_output(output)?.fs)
     }
-}
 
-impl Node {
-    fn name(&self) -> String {
-        match self {
-            Node::Folder(folder) => folder.name.to_string(),
-            Node::File(file) => file.name.to_string(),
-        }
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
     }
 
-    fn size(&self) -> usize {
-        match self {
-            Node::Folder(_) => self
-                .children()
-                .iter()
-                .map(|child| child.borrow().size())
-                .sum(),
-            Node::File(file) => file.size,
-        }
+    fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
     }
 
-    fn parent(&self) -> Option<Rc<RefCell<Node>>> {
-        match self {
-            Node::Folder(folder) => folder.parent.as_ref().map(Rc::clone),
-            Node::File(file) => Some(Rc::clone(&file.parent)),
-        }
+    pub fn name(&self, id: NodeId) -> &str {
+        &self.node(id).name
     }
 
-    fn children(&self) -> Vec<Rc<RefCell<Node>>> {
-        match self {
-            Node::Folder(folder) => folder.children.iter().map(Rc::clone).collect(),
-            Node::File(_) => panic!("{}: Not a directory", self.name()),
-        }
+    pub fn size(&self, id: NodeId) -> usize {
+        self.node(id).size
     }
 
-    fn get_child(&self, name: &str) -> Rc<RefCell<Node>> {
-        return Rc::clone(
-            self.children()
-                .iter()
-                .find(|child| child.borrow().name() == name)
-                .unwrap(),
-        );
+    pub fn kind(&self, id: NodeId) -> Kind {
+        self.node(id).kind
     }
 
-    fn add_child(&mut self, node: Rc<RefCell<Node>>) {
-        match self {
-            Node::Folder(folder) => folder.children.push(node),
-            Node::File(_) => panic!("{}: Not a directory", self.name()),
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        self.kind(id) == Kind::Folder
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.children(id)
+            .iter()
+            .find(|child| self.name(**child) == name)
+            .copied()
+    }
+
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.parent(node) {
+            names.push(self.name(node));
+            node = parent;
         }
+        names.reverse();
+        format!("/{}", names.join("/"))
     }
 
-    fn traverse(&self, callback: &mut impl FnMut(&Node)) {
-        callback(self);
-        match self {
-            Node::Folder(_) => {
-                self.children().iter().for_each(|child| {
-                    child.borrow().traverse(callback);
-                });
-            }
-            Node::File(_) => {}
+    fn add_child(&mut self, id: NodeId, name: String, kind: Kind, size: usize) -> NodeId {
+        if !self.is_dir(id) {
+            panic!("{}: Not a directory", self.name(id));
         }
+        let child = NodeId(self.nodes.len());
+        self.nodes.push(Node::new(name, kind, Some(id), size));
+        self.nodes[id.0].children.push(child);
+        child
     }
 
-    fn _as_tree_str(&self, depth: usize) -> String {
-        let mut output = format!("{}- {}", "  ".repeat(depth), self.name());
-        return match self {
-            Node::Folder(folder) => {
-                output += " (dir)";
-                folder.children.iter().fold(output, |output, child| {
-                    output + "\n" + child.borrow()._as_tree_str(depth + 1).as_str()
-                })
+    /// Recomputes directory sizes in a single pass. Since children always come after their parent,
+    /// walking the arena backwards visits every node after all of its descendants.
+    fn update_sizes(&mut self) {
+        for node in self.nodes.iter_mut() {
+            if node.kind == Kind::Folder {
+                node.size = 0;
             }
-            Node::File(_) => output + format!(" (file, size={})", self.size()).as_str(),
-        };
+        }
+        for i in (1..self.nodes.len()).rev() {
+            let size = self.nodes[i].size;
+            let parent = self.nodes[i].parent.unwrap();
+            self.nodes[parent.0].size += size;
+        }
+    }
+
+    /// Visits `id` and its descendants depth-first, parents before children, along with their
+    /// depth relative to `id`.
+    pub fn traverse(&self, id: NodeId, callback: &mut impl FnMut(NodeId, usize)) {
+        let mut stack = vec![(id, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            callback(id, depth);
+            stack.extend(
+                self.children(id)
+                    .iter()
+                    .rev()
+                    .map(|child| (*child, depth + 1)),
+            );
+        }
+    }
+
+    fn _as_tree_str(&self, id: NodeId, depth: usize) -> String {
+        let mut output = format!("{}- {}", "  ".repeat(depth), self.name(id));
+        if self.is_dir(id) {
+            output += " (dir)";
+            self.children(id).iter().fold(output, |output, child| {
+                output + "\n" + self._as_tree_str(*child, depth + 1).as_str()
+            })
+        } else {
+            output + format!(" (file, size={})", self.size(id)).as_str()
+        }
     }
 }
 
+/// Whether a name is a single path component, naming neither its directory nor the parent.
+fn is_valid_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains('/')
+}
+
 impl Session {
     fn new() -> Session {
-        let root = Rc::new(RefCell::new(Node::Folder(Folder::root())));
-        let node = Rc::clone(&root);
-        Session { root, node }
+        let fs = FileSystem::new();
+        let node = fs.root();
+        Session { fs, node }
     }
 
-    fn from_output(output: String) -> Session {
+    fn from_output(output: String) -> Result<Session, Error> {
         let mut commands_output = output.split("$ ");
         commands_output.next(); // Skip empty result before first `$`.
 
@@ -138,100 +198,191 @@ impl Session {
 
         for command_output in commands_output {
             let mut lines = command_output.lines();
-            let command = lines.next().unwrap();
-
-            if command.starts_with("cd") {
-                let path = command.split(' ').nth(1).unwrap();
-                session.cd(path)
-            } else if command.starts_with("ls") {
-                let list = lines.collect::<Vec<&str>>();
-                session.ls(list)
-            } else {
-                panic!("Unsupported command: {}", command);
+            let command = lines.next().unwrap_or_default();
+            let mut args = command.split_whitespace();
+
+            match args.next() {
+                Some("cd") => session.cd(args.next().unwrap_or("/"))?,
+                Some("ls") => session.ls(lines)?,
+                _ => return Err(Error::UnsupportedCommand(command.to_string())),
             }
         }
 
-        session
+        session.fs.update_sizes();
+        Ok(session)
     }
 
-    fn cd(&mut self, path: &str) {
-        self.node = match path {
-            "/" => Rc::clone(&self.root),
-            ".." => match Rc::clone(&self.node).borrow().parent() {
-                Some(parent) => Rc::clone(&parent),
-                None => return,
-            },
-            _ => Rc::clone(&self.node).borrow().get_child(path),
+    /// Changes directory along `path`, which is either absolute or relative to the current one.
+    fn cd(&mut self, path: &str) -> Result<(), Error> {
+        let mut node = if path.starts_with('/') {
+            self.fs.root()
+        } else {
+            self.node
+        };
+        for component in path.split('/') {
+            node = match component {
+                "" | "." => node,
+                ".." => self.fs.parent(node).unwrap_or(node),
+                name => match self.fs.child(node, name) {
+                    Some(child) if self.fs.is_dir(child) => child,
+                    Some(child) => return Err(Error::NotADirectory(self.fs.path(child))),
+                    None => return Err(Error::NoSuchDirectory(join(&self.fs.path(node), name))),
+                },
+            };
         }
+        self.node = node;
+        Ok(())
     }
 
-    fn ls(&self, list: Vec<&str>) {
+    /// Records a listing of the current directory, merging it with anything listed before.
+    fn ls<'a>(&mut self, list: impl Iterator<Item = &'a str>) -> Result<(), Error> {
         for line in list {
-            let node = if line.starts_with("dir") {
-                let name = line.split(' ').nth(1).unwrap().to_string();
-                Node::Folder(Folder::new(name, Some(Rc::clone(&self.node))))
-            } else {
-                let mut parts = line.split(' ');
-                let size = parts.next().unwrap().parse::<usize>().unwrap();
-                let name = parts.next().unwrap().to_string();
-                Node::File(File::new(name, Rc::clone(&self.node), size))
+            let invalid = || Error::InvalidListing(line.to_string());
+            let (size, name) = line.split_once(' ').ok_or_else(invalid)?;
+            if !is_valid_name(name) {
+                return Err(invalid());
+            }
+            let (kind, size) = match size {
+                "dir" => (Kind::Folder, 0),
+                _ => (Kind::File, size.parse::<usize>().map_err(|_| invalid())?),
             };
-            self.node
-                .borrow_mut()
-                .add_child(Rc::new(RefCell::new(node)));
+
+            match self.fs.child(self.node, name) {
+                Some(child) => {
+                    let path = self.fs.path(child);
+                    if self.fs.node(child).kind != kind {
+                        return Err(Error::ConflictingKind(path));
+                    }
+                    let recorded = self.fs.size(child);
+                    if kind == Kind::File && recorded != size {
+                        return Err(Error::ConflictingSize {
+                            path,
+                            recorded,
+                            listed: size,
+                        });
+                    }
+                }
+                None => {
+                    self.fs.add_child(self.node, name.to_string(), kind, size);
+                }
+            }
         }
+        Ok(())
     }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotADirectory(path) => write!(f, "{}: Not a directory", path),
+            Error::NoSuchDirectory(path) => write!(f, "{}: No such directory", path),
+            Error::ConflictingKind(path) => {
+                write!(f, "{}: Listed both as a file and as a directory", path)
+            }
+            Error::ConflictingSize {
+                path,
+                recorded,
+                listed,
+            } => write!(
+                f,
+                "{}: Listed with size {} after size {}",
+                path, listed, recorded
+            ),
+            Error::UnsupportedCommand(command) => write!(f, "Unsupported command: {}", command),
+            Error::InvalidListing(line) => write!(f, "Invalid listing: {}", line),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+fn join(path: &str, name: &str) -> String {
+    format!("{}/{}", path.trim_end_matches('/'), name)
+}
+
+fn sum_of_directories_up_to(fs: &FileSystem, limit: usize) -> usize {
+    fs.find(|entry| entry.is_dir() && entry.size() < limit)
+        .iter()
+        .map(Entry::size)
+        .sum()
+}
+
+fn smallest_directory(fs: &FileSystem, capacity: usize, to_free: usize) -> usize {
+    let limit = to_free - (capacity - fs.size(fs.root()));
+    fs.find(|entry| entry.is_dir() && entry.size() >= limit)
+        .iter()
+        .map(Entry::size)
+        .min()
+        .unwrap()
+}
+
 pub fn sum_total_size_of_directories_up_to(filename: String, limit: usize) -> usize {
     let input = fs::read_to_string(filename).unwrap();
-    let session = Session::from_output(input);
-    let mut sum = 0;
-    session
-        .root
-        .borrow()
-        .traverse(&mut |node: &Node| match node {
-            Node::Folder(_) => {
-                let size = node.size();
-                if size < limit {
-                    sum += size;
-                }
-            }
-            Node::File(_) => {}
-        });
-    sum
+    sum_of_directories_up_to(&FileSystem::from_output(input).unwrap(), limit)
 }
 
 pub fn smallest_directory_to_free_up(filename: String, capacity: usize, to_free: usize) -> usize {
     let input = fs::read_to_string(filename).unwrap();
-    let session = Session::from_output(input);
-    let limit = to_free - (capacity - session.root.borrow().size());
-    let mut smallest = session.root.borrow().size();
-    session
-        .root
-        .borrow()
-        .traverse(&mut |node: &Node| match node {
-            Node::Folder(_) => {
-                let size = node.size();
-                if size >= limit && size < smallest {
-                    smallest = size;
-                }
-            }
-            Node::File(_) => {}
-        });
-    smallest
+    smallest_directory(&FileSystem::from_output(input).unwrap(), capacity, to_free)
+}
+
+/// The puzzle's example, shared by the tests of every module.
+#[cfg(test)]
+pub(crate) fn example() -> FileSystem {
+    let input = fs::read_to_string("assets/example.txt").unwrap();
+    FileSystem::from_output(input).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use test_case::test_case;
+
+    /// Generates a transcript for a tree `depth` levels deep where every directory holds `breadth`
+    /// files and `breadth` subdirectories, returning it along with every directory's size.
+    fn synthetic_transcript(depth: usize, breadth: usize) -> (String, Vec<usize>) {
+        fn list(
+            output: &mut String,
+            sizes: &mut Vec<usize>,
+            seed: &mut u64,
+            depth: usize,
+            breadth: usize,
+        ) -> usize {
+            let mut total = 0;
+            *output += "$ ls\n";
+            for i in 0..breadth {
+                *seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let size = (*seed >> 33) as usize % 100000;
+                *output += format!("{} f{}.txt\n", size, i).as_str();
+                total += size;
+            }
+            if depth > 0 {
+                for i in 0..breadth {
+                    *output += format!("dir d{}\n", i).as_str();
+                }
+                for i in 0..breadth {
+                    *output += format!("$ cd d{}\n", i).as_str();
+                    total += list(output, sizes, seed, depth - 1, breadth);
+                    *output += "$ cd ..\n";
+                }
+            }
+            sizes.push(total);
+            total
+        }
+
+        let mut output = "$ cd /\n".to_string();
+        let mut sizes = Vec::new();
+        list(&mut output, &mut sizes, &mut 42, depth, breadth);
+        (output, sizes)
+    }
 
     #[test]
     fn example_parsing() {
-        let input = fs::read_to_string("assets/example.txt").unwrap();
-        let session = Session::from_output(input);
+        let fs = example();
         assert_eq!(
-            session.root.borrow()._as_tree_str(0),
+            fs._as_tree_str(fs.root(), 0),
             "
 - / (dir)
   - a (dir)
@@ -252,6 +403,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<FileSystem>();
+    }
+
+    #[test_case(6, 4)]
+    #[test_case(3000, 1)]
+    fn synthetic(depth: usize, breadth: usize) {
+        let (transcript, sizes) = synthetic_transcript(depth, breadth);
+        let fs = FileSystem::from_output(transcript).unwrap();
+        let root = *sizes.last().unwrap();
+        assert_eq!(fs.size(fs.root()), root);
+
+        let limit = root / 3;
+        let expected: usize = sizes.iter().filter(|size| **size < limit).sum();
+        assert_eq!(sum_of_directories_up_to(&fs, limit), expected);
+
+        let (capacity, to_free) = (root * 2, root + root / 4);
+        let needed = to_free - (capacity - root);
+        let expected = *sizes.iter().filter(|size| **size >= needed).min().unwrap();
+        assert_eq!(smallest_directory(&fs, capacity, to_free), expected);
+    }
+
+    #[test]
+    fn messy_transcript() {
+        let transcript = "
+$ cd /
+$ ls
+dir a
+1 b.txt
+$ cd a/
+$ ls
+dir c
+2 d
+$ cd ./c
+$ ls
+3 e
+$ cd ../../a/c/..
+$ ls
+2 d
+dir c
+4 f
+$ cd /a/c
+$ ls
+3 e
+$ cd
+$ ls
+1 b.txt
+";
+        let fs = FileSystem::from_output(transcript.to_string()).unwrap();
+        assert_eq!(
+            fs._as_tree_str(fs.root(), 0),
+            "
+- / (dir)
+  - a (dir)
+    - c (dir)
+      - e (file, size=3)
+    - d (file, size=2)
+    - f (file, size=4)
+  - b.txt (file, size=1)
+            "
+            .trim()
+        );
+        assert_eq!(fs.size(fs.root()), 10);
+    }
+
+    #[test_case("$ cd /\n$ ls\n1 a\n$ cd a", Error::NotADirectory("/a".to_string()))]
+    #[test_case("$ cd /\n$ ls\ndir a\n$ cd a/b", Error::NoSuchDirectory("/a/b".to_string()))]
+    #[test_case("$ cd /\n$ ls\ndir a\n$ ls\n1 a", Error::ConflictingKind("/a".to_string()))]
+    #[test_case(
+        "$ cd /\n$ ls\n1 a\n$ ls\n2 a",
+        Error::ConflictingSize { path: "/a".to_string(), recorded: 1, listed: 2 }
+    )]
+    #[test_case("$ cd /\n$ rm a", Error::UnsupportedCommand("rm a".to_string()))]
+    #[test_case("$ cd /\n$ ls\nx a", Error::InvalidListing("x a".to_string()))]
+    #[test_case("$ cd /\n$ ls\n1 ", Error::InvalidListing("1 ".to_string()))]
+    #[test_case("$ cd /\n$ ls\n2 .", Error::InvalidListing("2 .".to_string()))]
+    #[test_case("$ cd /\n$ ls\ndir ..", Error::InvalidListing("dir ..".to_string()))]
+    #[test_case("$ cd /\n$ ls\n1 a/b", Error::InvalidListing("1 a/b".to_string()))]
+    fn transcript_errors(transcript: &str, expected: Error) {
+        let result = Session::from_output(transcript.to_string());
+        assert_eq!(result.err(), Some(expected));
+    }
+
     #[test]
     fn part1() {
         assert_eq!(
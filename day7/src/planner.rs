@@ -0,0 +1,345 @@
+use super::{Entry, FileSystem, NodeId};
+use std::cmp::Reverse;
+use std::fmt;
+
+/// Steps the least bytes search takes at most.
+const SEARCH_LIMIT: usize = 1_000_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    /// Delete the smallest directory that frees enough space.
+    SingleDirectory,
+    /// Delete as few files or directories as possible, then as few bytes as possible.
+    FewestItems,
+    /// Delete as few bytes as possible, grouping files into their directory when all of its
+    /// contents go.
+    LeastBytes,
+}
+
+/// Files and directories to delete to free up space, none of them nested in another.
+pub struct Plan<'a> {
+    pub strategy: Strategy,
+    pub capacity: usize,
+    pub used: usize,
+    pub to_free: usize,
+    pub items: Vec<Entry<'a>>,
+}
+
+impl<'a> Plan<'a> {
+    pub fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.used)
+    }
+
+    pub fn needed(&self) -> usize {
+        self.to_free.saturating_sub(self.free())
+    }
+
+    pub fn freed(&self) -> usize {
+        self.items.iter().map(Entry::size).sum()
+    }
+}
+
+impl FileSystem {
+    /// Plans which entries to delete so that at least `to_free` is free out of `capacity`, or
+    /// `None` if the strategy finds nothing enough to delete. The root never gets deleted.
+    pub fn plan_cleanup(
+        &self,
+        capacity: usize,
+        to_free: usize,
+        strategy: Strategy,
+    ) -> Option<Plan<'_>> {
+        let used = self.size(self.root());
+        let mut plan = Plan {
+            strategy,
+            capacity,
+            used,
+            to_free,
+            items: Vec::new(),
+        };
+        let needed = plan.needed();
+        if needed > used {
+            return None;
+        }
+        if needed > 0 {
+            let root = self.root();
+            plan.items = match strategy {
+                Strategy::SingleDirectory => vec![smallest(self.find(|entry| {
+                    entry.id() != root && entry.is_dir() && entry.size() >= needed
+                }))?],
+                Strategy::FewestItems => self.fewest_items(needed),
+                Strategy::LeastBytes => self.least_bytes(needed),
+            };
+        }
+        Some(plan)
+    }
+
+    /// Finds the smallest set of files adding up to at least `needed` and groups them into
+    /// directories where possible. Any set of non-nested entries frees the same space as the files
+    /// under them, so this is a subset sum over files, solved by a branch and bound search over
+    /// files from largest to smallest. Its cost depends on the number of files rather than their
+    /// sizes, but it gives up after `SEARCH_LIMIT` steps, keeping the best set found so far, which
+    /// may then free more than the minimum.
+    fn least_bytes(&self, needed: usize) -> Vec<Entry<'_>> {
+        let mut files = self.find(|entry| !entry.is_dir() && entry.size() > 0);
+        files.sort_by_key(|file| Reverse(file.size()));
+        // Total size of the files from each index on.
+        let mut rest = vec![0; files.len() + 1];
+        for i in (0..files.len()).rev() {
+            rest[i] = rest[i + 1] + files[i].size();
+        }
+
+        // Deleting every file always frees enough.
+        let mut best = (rest[0], (0..files.len()).collect::<Vec<usize>>());
+        let (mut path, mut sum, mut i) = (Vec::new(), 0, 0);
+        for _ in 0..SEARCH_LIMIT {
+            if i < files.len() && sum + rest[i] >= needed {
+                let size = files[i].size();
+                let first = path.last().map_or(0, |last| last + 1);
+                // Taking a file instead of a skipped one of the same size finds nothing new.
+                let repeat = i > first && files[i - 1].size() == size;
+                if repeat || sum + size >= best.0 {
+                    i += 1;
+                } else if sum + size >= needed {
+                    best = (sum + size, path.iter().copied().chain([i]).collect());
+                    if best.0 == needed {
+                        break;
+                    }
+                    i += 1;
+                } else {
+                    path.push(i);
+                    sum += size;
+                    i += 1;
+                }
+            } else {
+                match path.pop() {
+                    Some(last) => {
+                        sum -= files[last].size();
+                        i = last + 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        let mut chosen = vec![false; self.nodes.len()];
+        for i in best.1 {
+            chosen[files[i].id().0] = true;
+        }
+
+        // A directory goes as a whole when everything under it goes.
+        for id in self.postorder() {
+            chosen[id.0] = if self.is_dir(id) {
+                self.children(id).iter().all(|child| chosen[child.0])
+            } else {
+                chosen[id.0] || self.size(id) == 0
+            };
+        }
+        self.chosen_items(&chosen)
+    }
+
+    /// Finds as few non-nested entries as possible adding up to at least `needed`, then as few
+    /// bytes as possible, by a branch and bound search over entries from largest to smallest. It
+    /// gives up after `SEARCH_LIMIT` steps like `least_bytes` does.
+    fn fewest_items(&self, needed: usize) -> Vec<Entry<'_>> {
+        let root = self.root();
+        let mut entries = self.find(|entry| entry.id() != root && entry.size() > 0);
+        entries.sort_by_key(|entry| Reverse(entry.size()));
+        // Total size of the entries before each index.
+        let mut prefix = vec![0; entries.len() + 1];
+        for (i, entry) in entries.iter().enumerate() {
+            prefix[i + 1] = prefix[i] + entry.size();
+        }
+
+        // Deleting everything in the root always frees enough.
+        let top = (0..entries.len())
+            .filter(|i| self.parent(entries[*i].id()) == Some(root))
+            .collect::<Vec<usize>>();
+        let mut best = ((top.len(), self.size(root)), top);
+        // Whether each node is chosen, and how many chosen nodes are under it.
+        let mut taken = vec![false; self.nodes.len()];
+        let mut below = vec![0; self.nodes.len()];
+        let ancestors = |id: NodeId| std::iter::successors(self.parent(id), |id| self.parent(*id));
+        let (mut path, mut sum, mut i) = (Vec::new(), 0, 0);
+        for _ in 0..SEARCH_LIMIT {
+            // The most the entries left can add without taking more items than the best.
+            let slots = best.0 .0 - path.len();
+            let most = prefix[(i + slots).min(entries.len())] - prefix[i.min(entries.len())];
+            if i < entries.len() && sum + most >= needed {
+                let (id, size) = (entries[i].id(), entries[i].size());
+                let nested = below[id.0] > 0 || ancestors(id).any(|ancestor| taken[ancestor.0]);
+                if nested || (path.len() + 1, sum + size) >= best.0 {
+                    i += 1;
+                } else if sum + size >= needed {
+                    best = (
+                        (path.len() + 1, sum + size),
+                        path.iter().copied().chain([i]).collect(),
+                    );
+                    i += 1;
+                } else {
+                    path.push(i);
+                    sum += size;
+                    taken[id.0] = true;
+                    ancestors(id).for_each(|ancestor| below[ancestor.0] += 1);
+                    i += 1;
+                }
+            } else {
+                match path.pop() {
+                    Some(last) => {
+                        let id = entries[last].id();
+                        sum -= entries[last].size();
+                        taken[id.0] = false;
+                        ancestors(id).for_each(|ancestor| below[ancestor.0] -= 1);
+                        i = last + 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        let mut chosen = vec![false; self.nodes.len()];
+        for i in best.1 {
+            chosen[entries[i].id().0] = true;
+        }
+        self.chosen_items(&chosen)
+    }
+
+    /// The chosen entries with something in them, outside of the root and in listing order,
+    /// skipping what is under a chosen directory.
+    fn chosen_items(&self, chosen: &[bool]) -> Vec<Entry<'_>> {
+        let mut items = Vec::new();
+        let mut stack = self
+            .children(self.root())
+            .iter()
+            .rev()
+            .copied()
+            .collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            if chosen[id.0] {
+                if self.size(id) > 0 {
+                    items.push(self.entry(id));
+                }
+            } else {
+                stack.extend(self.children(id).iter().rev());
+            }
+        }
+        items
+    }
+}
+
+fn smallest<'a>(entries: Vec<Entry<'a>>) -> Option<Entry<'a>> {
+    entries.into_iter().min_by_key(Entry::size)
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::SingleDirectory => write!(f, "single directory"),
+            Strategy::FewestItems => write!(f, "fewest items"),
+            Strategy::LeastBytes => write!(f, "least bytes"),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Plan<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Using {} of {}, {} free.",
+            self.used,
+            self.capacity,
+            self.free()
+        )?;
+        if self.items.is_empty() {
+            return writeln!(f, "Already {} free, nothing to delete.", self.to_free);
+        }
+        writeln!(f, "Freeing {} needs {} more.", self.to_free, self.needed())?;
+        writeln!(
+            f,
+            "Delete {} item(s) ({}), freeing {} ({} over target):",
+            self.items.len(),
+            self.strategy,
+            self.freed(),
+            self.freed() - self.needed()
+        )?;
+        for item in self.items.iter() {
+            writeln!(f, "  {}\t{}", item.size(), item.path())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use test_case::test_case;
+
+    #[test_case(30000000, Strategy::SingleDirectory, vec!["/d"])]
+    #[test_case(30000000, Strategy::FewestItems, vec!["/c.dat"])]
+    #[test_case(30000000, Strategy::LeastBytes, vec!["/c.dat"])]
+    #[test_case(25718835, Strategy::LeastBytes, vec!["/a/h.lst", "/d/j"])]
+    #[test_case(25773862, Strategy::LeastBytes, vec!["/a", "/d/j"])]
+    #[test_case(20000000, Strategy::LeastBytes, vec![])]
+    #[test_case(60000000, Strategy::FewestItems, vec!["/b.txt", "/d"])]
+    #[test_case(60000000, Strategy::LeastBytes, vec!["/b.txt", "/c.dat", "/d/d.log", "/d/k"])]
+    #[test_case(70000000, Strategy::FewestItems, vec!["/a", "/b.txt", "/c.dat", "/d"])]
+    #[test_case(70000000, Strategy::LeastBytes, vec!["/a", "/b.txt", "/c.dat", "/d"])]
+    fn plan_cleanup(to_free: usize, strategy: Strategy, expected: Vec<&str>) {
+        let fs = example();
+        let plan = fs.plan_cleanup(70000000, to_free, strategy).unwrap();
+        let paths = plan.items.iter().map(Entry::path).collect::<Vec<String>>();
+        assert_eq!(paths, expected);
+    }
+
+    #[test_case(5700000000, vec!["/a/x.bin", "/a/y.bin"])]
+    #[test_case(6900000000, vec!["/a"])]
+    #[test_case(9000000000, vec!["/a/y.bin", "/big.iso"])]
+    fn least_bytes_large(to_free: usize, expected: Vec<&str>) {
+        let transcript = "
+$ cd /
+$ ls
+dir a
+5000000000 big.iso
+$ cd a
+$ ls
+1500000000 x.bin
+2500000000 y.bin
+1200000000 z.bin
+";
+        let fs = FileSystem::from_output(transcript.to_string()).unwrap();
+        let plan = fs
+            .plan_cleanup(12000000000, to_free, Strategy::LeastBytes)
+            .unwrap();
+        let paths = plan.items.iter().map(Entry::path).collect::<Vec<String>>();
+        assert_eq!(paths, expected);
+    }
+
+    #[test_case(60000000)]
+    #[test_case(70000000)]
+    fn no_single_directory(to_free: usize) {
+        let fs = example();
+        assert!(fs
+            .plan_cleanup(70000000, to_free, Strategy::SingleDirectory)
+            .is_none());
+    }
+
+    #[test]
+    fn explain() {
+        let fs = example();
+        let plan = fs
+            .plan_cleanup(70000000, 25773862, Strategy::LeastBytes)
+            .unwrap();
+        assert_eq!(
+            plan.to_string(),
+            "Using 48381165 of 70000000, 21618835 free.
+Freeing 25773862 needs 4155027 more.
+Delete 2 item(s) (least bytes), freeing 4155027 (0 over target):
+  94853\t/a
+  4060174\t/d/j
+"
+        );
+        assert!(fs
+            .plan_cleanup(70000000, 80000000, Strategy::FewestItems)
+            .is_none());
+    }
+}
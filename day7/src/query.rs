@@ -0,0 +1,274 @@
+use super::{FileSystem, Kind, NodeId};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// A node along with the file system it belongs to, for convenient querying.
+#[derive(Clone, Copy)]
+pub struct Entry<'a> {
+    fs: &'a FileSystem,
+    id: NodeId,
+    depth: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DepthStats {
+    /// Number of entries at each depth, the root being at depth 0.
+    pub entries_per_depth: Vec<usize>,
+    pub max_depth: usize,
+    pub mean_file_depth: f64,
+}
+
+impl<'a> Entry<'a> {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.fs.name(self.id)
+    }
+
+    pub fn path(&self) -> String {
+        self.fs.path(self.id)
+    }
+
+    pub fn size(&self) -> usize {
+        self.fs.size(self.id)
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.fs.kind(self.id)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.fs.is_dir(self.id)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Extension of a file's name, if it has one.
+    pub fn extension(&self) -> Option<&'a str> {
+        if self.is_dir() {
+            return None;
+        }
+        match self.name().rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => Some(extension),
+            _ => None,
+        }
+    }
+}
+
+impl FileSystem {
+    pub fn entry(&self, id: NodeId) -> Entry<'_> {
+        let mut depth = 0;
+        let mut node = id;
+        while let Some(parent) = self.parent(node) {
+            depth += 1;
+            node = parent;
+        }
+        Entry {
+            fs: self,
+            id,
+            depth,
+        }
+    }
+
+    /// Entries matching `predicate`, parents before children.
+    pub fn find(&self, predicate: impl Fn(&Entry) -> bool) -> Vec<Entry<'_>> {
+        let mut found = Vec::new();
+        self.traverse(self.root(), &mut |id, depth| {
+            let entry = Entry {
+                fs: self,
+                id,
+                depth,
+            };
+            if predicate(&entry) {
+                found.push(entry);
+            }
+        });
+        found
+    }
+
+    /// Entries whose path matches `pattern`, where `*` and `?` match within a path component and
+    /// `**` matches any number of components. Patterns not starting with `/` match at any depth.
+    /// Only `/` itself matches the root.
+    pub fn glob(&self, pattern: &str) -> Vec<Entry<'_>> {
+        let mut parts = components(pattern);
+        if !pattern.starts_with('/') {
+            parts.insert(0, vec!['*', '*']);
+        }
+        self.find(|entry| {
+            let path = components(&entry.path());
+            (!path.is_empty() || parts.is_empty()) && matches(&parts, &path)
+        })
+    }
+
+    /// The `n` largest entries of the given kind, largest first.
+    pub fn largest(&self, kind: Kind, n: usize) -> Vec<Entry<'_>> {
+        let mut entries = self.find(|entry| entry.kind() == kind);
+        entries.sort_by_key(|entry| Reverse(entry.size()));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Total size of files by extension, where files without one are under an empty extension.
+    pub fn size_by_extension(&self) -> BTreeMap<String, usize> {
+        let mut sizes = BTreeMap::new();
+        for entry in self.find(|entry| !entry.is_dir()) {
+            let extension = entry.extension().unwrap_or_default().to_string();
+            *sizes.entry(extension).or_insert(0) += entry.size();
+        }
+        sizes
+    }
+
+    pub fn depth_stats(&self) -> DepthStats {
+        let mut entries_per_depth = Vec::new();
+        let (mut files, mut file_depths) = (0, 0);
+        self.traverse(self.root(), &mut |id, depth| {
+            if entries_per_depth.len() <= depth {
+                entries_per_depth.resize(depth + 1, 0);
+            }
+            entries_per_depth[depth] += 1;
+            if !self.is_dir(id) {
+                files += 1;
+                file_depths += depth;
+            }
+        });
+        DepthStats {
+            max_depth: entries_per_depth.len() - 1,
+            entries_per_depth,
+            mean_file_depth: if files > 0 {
+                file_depths as f64 / files as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Total size of every directory, subdirectories before their parent like `du` does.
+    pub fn du(&self) -> Vec<(String, usize)> {
+        self.postorder()
+            .into_iter()
+            .filter(|id| self.is_dir(*id))
+            .map(|id| (self.path(id), self.size(id)))
+            .collect()
+    }
+
+    /// Every node, children before their parent and siblings in listing order.
+    pub(crate) fn postorder(&self) -> Vec<NodeId> {
+        let mut order = Vec::new();
+        let mut stack = vec![self.root()];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.children(id));
+        }
+        order.reverse();
+        order
+    }
+}
+
+/// Names along a path, where every path starts from the root.
+fn components(path: &str) -> Vec<Vec<char>> {
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .map(|component| component.chars().collect::<Vec<char>>())
+        .collect()
+}
+
+fn matches(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((component, rest)) if *component == ['*', '*'] => {
+            (0..=path.len()).any(|i| matches(rest, &path[i..]))
+        }
+        Some((component, rest)) => match path.split_first() {
+            Some((name, path)) => matches_component(component, name) && matches(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn matches_component(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches_component(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && matches_component(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_component(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+    use test_case::test_case;
+
+    fn paths(entries: Vec<Entry>) -> Vec<String> {
+        entries.iter().map(Entry::path).collect()
+    }
+
+    #[test]
+    fn find() {
+        let fs = example();
+        assert_eq!(
+            paths(fs.find(|entry| !entry.is_dir() && entry.size() > 8000000)),
+            vec!["/b.txt", "/c.dat", "/d/d.log"]
+        );
+    }
+
+    #[test_case("*.txt", vec!["/b.txt"])]
+    #[test_case("/a/**", vec!["/a", "/a/e", "/a/e/i", "/a/f", "/a/g", "/a/h.lst"])]
+    #[test_case("/d/?.*", vec!["/d/d.log", "/d/d.ext"])]
+    #[test_case("/*/e", vec!["/a/e"])]
+    #[test_case("e/*", vec!["/a/e/i"])]
+    #[test_case("/?", vec!["/a", "/d"])]
+    fn glob(pattern: &str, expected: Vec<&str>) {
+        assert_eq!(paths(example().glob(pattern)), expected);
+    }
+
+    #[test]
+    fn glob_root() {
+        let fs = example();
+        for pattern in ["*", "?", "**", "/*", "/**"] {
+            assert!(!paths(fs.glob(pattern)).contains(&"/".to_string()));
+        }
+        assert_eq!(fs.glob("*").len(), 13);
+        assert_eq!(paths(fs.glob("/")), vec!["/"]);
+    }
+
+    #[test]
+    fn aggregates() {
+        let fs = example();
+        assert_eq!(paths(fs.largest(Kind::Folder, 2)), vec!["/", "/d"]);
+        assert_eq!(paths(fs.largest(Kind::File, 1)), vec!["/b.txt"]);
+        assert_eq!(
+            fs.size_by_extension(),
+            BTreeMap::from([
+                ("".to_string(), 11306727),
+                ("dat".to_string(), 8504156),
+                ("ext".to_string(), 5626152),
+                ("log".to_string(), 8033020),
+                ("lst".to_string(), 62596),
+                ("txt".to_string(), 14848514),
+            ])
+        );
+        assert_eq!(
+            fs.depth_stats(),
+            DepthStats {
+                entries_per_depth: vec![1, 4, 8, 1],
+                max_depth: 3,
+                mean_file_depth: 1.9,
+            }
+        );
+        assert_eq!(
+            fs.du(),
+            vec![
+                ("/a/e".to_string(), 584),
+                ("/a".to_string(), 94853),
+                ("/d".to_string(), 24933642),
+                ("/".to_string(), 48381165),
+            ]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use super::{FileSystem, NodeId};
+
+impl FileSystem {
+    /// Renders the file system like `tree` does, with the size of every entry.
+    pub fn tree(&self, human_readable: bool) -> String {
+        fn children(
+            fs: &FileSystem,
+            id: NodeId,
+            prefix: &str,
+            human_readable: bool,
+            output: &mut String,
+        ) {
+            let count = fs.children(id).len();
+            for (i, child) in fs.children(id).iter().enumerate() {
+                let last = i == count - 1;
+                *output += format!(
+                    "{}{}{} ({})\n",
+                    prefix,
+                    if last { "└── " } else { "├── " },
+                    fs.name(*child),
+                    format_size(fs.size(*child), human_readable)
+                )
+                .as_str();
+                if fs.is_dir(*child) {
+                    let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                    children(fs, *child, &prefix, human_readable, output);
+                }
+            }
+        }
+
+        let root = self.root();
+        let mut output = format!(
+            "{} ({})\n",
+            self.name(root),
+            format_size(self.size(root), human_readable)
+        );
+        children(self, root, "", human_readable, &mut output);
+        output
+    }
+
+    /// Lists the size and path of every entry like `du -a` does, children before their parent.
+    pub fn du_all(&self, human_readable: bool) -> String {
+        self.postorder()
+            .into_iter()
+            .map(|id| {
+                format!(
+                    "{}\t{}\n",
+                    format_size(self.size(id), human_readable),
+                    self.path(id)
+                )
+            })
+            .collect()
+    }
+
+    /// Exports the hierarchy as JSON, with directories holding their children.
+    pub fn to_json(&self) -> String {
+        fn node(fs: &FileSystem, id: NodeId, output: &mut String) {
+            *output += format!(
+                "{{\"name\":{},\"type\":\"{}\",\"size\":{}",
+                json_string(fs.name(id)),
+                if fs.is_dir(id) { "dir" } else { "file" },
+                fs.size(id)
+            )
+            .as_str();
+            if fs.is_dir(id) {
+                *output += ",\"children\":[";
+                for (i, child) in fs.children(id).iter().enumerate() {
+                    if i > 0 {
+                        *output += ",";
+                    }
+                    node(fs, *child, output);
+                }
+                *output += "]";
+            }
+            *output += "}";
+        }
+
+        let mut output = String::new();
+        node(self, self.root(), &mut output);
+        output
+    }
+
+    /// Exports every file as a folded stack of its path components followed by its size, the
+    /// input format of flame graph tools.
+    pub fn folded(&self) -> String {
+        self.find(|entry| !entry.is_dir())
+            .iter()
+            .map(|entry| {
+                let mut stack = vec![entry.name()];
+                let mut node = entry.id();
+                while let Some(parent) = self.parent(node) {
+                    stack.push(self.name(parent));
+                    node = parent;
+                }
+                stack.reverse();
+                format!("{} {}\n", stack.join(";"), entry.size())
+            })
+            .collect()
+    }
+}
+
+/// Formats a size in bytes, or in powers of 1024 with a unit suffix like `du -h` does.
+fn format_size(size: usize, human_readable: bool) -> String {
+    if !human_readable || size < 1024 {
+        return size.to_string();
+    }
+    let units = ["K", "M", "G", "T", "P", "E"];
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1}{}", value, units[unit])
+    } else {
+        format!("{:.0}{}", value, units[unit])
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output += "\\\"",
+            '\\' => output += "\\\\",
+            '\n' => output += "\\n",
+            '\t' => output += "\\t",
+            c if c.is_control() => output += format!("\\u{:04x}", c as u32).as_str(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+
+    #[test]
+    fn tree() {
+        assert_eq!(
+            example().tree(true).trim(),
+            "
+/ (46M)
+├── a (93K)
+│   ├── e (584)
+│   │   └── i (584)
+│   ├── f (28K)
+│   ├── g (2.5K)
+│   └── h.lst (61K)
+├── b.txt (14M)
+├── c.dat (8.1M)
+└── d (24M)
+    ├── j (3.9M)
+    ├── d.log (7.7M)
+    ├── d.ext (5.4M)
+    └── k (6.9M)
+            "
+            .trim()
+        );
+    }
+
+    #[test]
+    fn du_all() {
+        assert_eq!(
+            example().du_all(false),
+            "584\t/a/e/i
+584\t/a/e
+29116\t/a/f
+2557\t/a/g
+62596\t/a/h.lst
+94853\t/a
+14848514\t/b.txt
+8504156\t/c.dat
+4060174\t/d/j
+8033020\t/d/d.log
+5626152\t/d/d.ext
+7214296\t/d/k
+24933642\t/d
+48381165\t/
+"
+        );
+    }
+
+    #[test]
+    fn to_json() {
+        let input = "$ cd /\n$ ls\ndir a\n1 \"b\"\n$ cd a\n$ ls\n2 c\n";
+        let fs = FileSystem::from_output(input.to_string()).unwrap();
+        assert_eq!(
+            fs.to_json(),
+            r#"{"name":"/","type":"dir","size":3,"children":[{"name":"a","type":"dir","size":2,"children":[{"name":"c","type":"file","size":2}]},{"name":"\"b\"","type":"file","size":1}]}"#
+        );
+    }
+
+    #[test]
+    fn folded() {
+        let folded = example().folded();
+        let mut lines = folded.lines();
+        assert_eq!(lines.next(), Some("/;a;e;i 584"));
+        assert_eq!(lines.next(), Some("/;a;f 29116"));
+        assert_eq!(lines.last(), Some("/;d;k 7214296"));
+    }
+}
@@ -0,0 +1,219 @@
+use grid::Grid;
+use std::io::Write;
+
+/// Colors from low to high values, approximating the viridis color map.
+const PALETTE: [(u8, u8, u8); 5] = [
+    (68, 1, 84),
+    (59, 82, 139),
+    (33, 145, 140),
+    (94, 201, 98),
+    (253, 231, 37),
+];
+
+/// 3x5 pixel glyphs for legend labels, one row per byte with the leftmost pixel in bit 2.
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 12] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+];
+const FONT_SCALE: usize = 2;
+const LEGEND_MARGIN: usize = 8;
+const LEGEND_BAR_WIDTH: usize = 12;
+
+/// A grid of values rendered with colors scaled between their minimum and maximum.
+pub struct Heatmap {
+    rows: usize,
+    cols: usize,
+    values: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+impl Heatmap {
+    pub fn new<T>(grid: &Grid<T>, value: impl Fn(&T) -> f64) -> Heatmap {
+        let values = grid.iter().map(value).collect::<Vec<f64>>();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        Heatmap {
+            rows: grid.rows(),
+            cols: grid.cols(),
+            values,
+            min: if min.is_finite() { min } else { 0.0 },
+            max: if max.is_finite() { max } else { 0.0 },
+        }
+    }
+
+    pub fn heights(map: &Grid<i8>) -> Heatmap {
+        Heatmap::new(map, |height| *height as f64)
+    }
+
+    pub fn visibility(visible: &Grid<bool>) -> Heatmap {
+        Heatmap::new(visible, |visible| if *visible { 1.0 } else { 0.0 })
+    }
+
+    pub fn scenic_scores(scores: &Grid<usize>) -> Heatmap {
+        Heatmap::new(scores, |score| *score as f64)
+    }
+
+    fn color(&self, value: f64) -> (u8, u8, u8) {
+        let t = if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        };
+        let position = t.clamp(0.0, 1.0) * (PALETTE.len() - 1) as f64;
+        let index = (position.floor() as usize).min(PALETTE.len() - 2);
+        let fraction = position - index as f64;
+        let (from, to) = (PALETTE[index], PALETTE[index + 1]);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * fraction).round() as u8;
+        (mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+    }
+
+    fn label(value: f64) -> String {
+        if value.fract() == 0.0 {
+            format!("{}", value as i64)
+        } else {
+            format!("{:.2}", value)
+        }
+    }
+
+    /// Writes the heatmap as an RGB PNG with `scale` pixels per tree, followed on the right by a
+    /// color bar labelled with the maximum at the top and the minimum at the bottom.
+    pub fn write_png(&self, writer: impl Write, scale: usize) -> Result<(), png::EncodingError> {
+        let (max_label, min_label) = (Heatmap::label(self.max), Heatmap::label(self.min));
+        let glyph_advance = (GLYPH_WIDTH + 1) * FONT_SCALE;
+        let label_height = GLYPH_HEIGHT * FONT_SCALE;
+        let label_width = max_label.len().max(min_label.len()) * glyph_advance;
+
+        let bar_x = self.cols * scale + LEGEND_MARGIN;
+        let label_x = bar_x + LEGEND_BAR_WIDTH + LEGEND_MARGIN / 2;
+        let width = label_x + label_width + LEGEND_MARGIN / 2;
+        let height = (self.rows * scale).max(label_height * 4);
+
+        let mut image = vec![255; width * height * 3];
+        let mut put = |x: usize, y: usize, (r, g, b): (u8, u8, u8)| {
+            let offset = (y * width + x) * 3;
+            image[offset..offset + 3].copy_from_slice(&[r, g, b]);
+        };
+
+        // Trees.
+        for (index, value) in self.values.iter().enumerate() {
+            let color = self.color(*value);
+            let (row, col) = (index / self.cols, index % self.cols);
+            for y in row * scale..(row + 1) * scale {
+                for x in col * scale..(col + 1) * scale {
+                    put(x, y, color);
+                }
+            }
+        }
+
+        // Color bar, from the maximum at the top to the minimum at the bottom.
+        for y in 0..height {
+            let t = 1.0 - y as f64 / (height - 1).max(1) as f64;
+            let color = self.color(self.min + (self.max - self.min) * t);
+            for x in bar_x..bar_x + LEGEND_BAR_WIDTH {
+                put(x, y, color);
+            }
+        }
+
+        // Labels.
+        for (label, top) in [(max_label, 0), (min_label, height - label_height)] {
+            for (i, c) in label.chars().enumerate() {
+                let rows = match GLYPHS.iter().find(|(glyph, _)| *glyph == c) {
+                    Some((_, rows)) => rows,
+                    None => continue,
+                };
+                for (gy, bits) in rows.iter().enumerate() {
+                    for gx in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                            continue;
+                        }
+                        for dy in 0..FONT_SCALE {
+                            for dx in 0..FONT_SCALE {
+                                let x = label_x + i * glyph_advance + gx * FONT_SCALE + dx;
+                                let y = top + gy * FONT_SCALE + dy;
+                                put(x, y, (0, 0, 0));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&image)
+    }
+
+    /// Renders the heatmap for terminals supporting 24-bit colors, two columns per tree, followed
+    /// by a legend going from the minimum to the maximum.
+    pub fn to_ansi(&self) -> String {
+        let block = |(r, g, b): (u8, u8, u8)| format!("\x1b[48;2;{};{};{}m  ", r, g, b);
+        let mut output = String::new();
+        for row in self.values.chunks(self.cols.max(1)) {
+            for value in row {
+                output += block(self.color(*value)).as_str();
+            }
+            output += "\x1b[0m\n";
+        }
+        output += Heatmap::label(self.min).as_str();
+        output += " ";
+        for step in 0..10 {
+            let value = self.min + (self.max - self.min) * step as f64 / 9.0;
+            output += block(self.color(value)).as_str();
+        }
+        output += "\x1b[0m ";
+        output += Heatmap::label(self.max).as_str();
+        output += "\n";
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_map, scenic_scores};
+
+    #[test]
+    fn png() {
+        let map = parse_map("assets/example.txt".to_string());
+        let mut data = Vec::new();
+        Heatmap::scenic_scores(&scenic_scores(&map))
+            .write_png(&mut data, 4)
+            .unwrap();
+
+        let mut reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let mut image = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut image).unwrap();
+        // 5 trees of 4 pixels, then the margin, bar and a 1-character label.
+        assert_eq!((info.width, info.height), (20 + 8 + 12 + 4 + 8 + 4, 40));
+        // The best spot at (3, 2) has the highest score.
+        let offset = ((3 * 4) * info.width as usize + 2 * 4) * 3;
+        assert_eq!(&image[offset..offset + 3], &[253, 231, 37]);
+        // Edge trees score 0, the lowest.
+        assert_eq!(&image[0..3], &[68, 1, 84]);
+    }
+
+    #[test]
+    fn ansi() {
+        let map = parse_map("assets/example.txt".to_string());
+        let ansi = Heatmap::heights(&map).to_ansi();
+        let lines = ansi.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("\x1b[48;2;"));
+        assert_eq!(lines[0].matches("  ").count(), 5);
+        assert!(lines[5].starts_with("0 ") && lines[5].ends_with(" 9"));
+    }
+}
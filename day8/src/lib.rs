@@ -1,9 +1,12 @@
-use grid::Grid;
 use itertools::Itertools;
-use std::collections::HashSet;
 use std::fs;
 
-fn parse_map(filename: String) -> Grid<i8> {
+mod heatmap;
+
+pub use grid::Grid;
+pub use heatmap::Heatmap;
+
+pub fn parse_map(filename: String) -> Grid<i8> {
     let input = fs::read_to_string(filename).unwrap();
     let lines = input.lines();
 
@@ -18,66 +21,262 @@ fn parse_map(filename: String) -> Grid<i8> {
     map
 }
 
+/// Marks the trees visible from any edge, scanning every row and column once in each direction.
+pub fn visible_from_edges(map: &Grid<i8>) -> Grid<bool> {
+    let (rows, cols) = map.size();
+    let mut visible = Grid::init(rows, cols, false);
+
+    // Marks trees taller than every tree before them along `line`, stopping at the tallest height.
+    fn scan(map: &Grid<i8>, visible: &mut Grid<bool>, line: impl Iterator<Item = (usize, usize)>) {
+        let mut tallest: i8 = -1;
+        for (i, j) in line {
+            let current = map[i][j];
+            if current > tallest {
+                tallest = current;
+                visible[i][j] = true;
+                if tallest == 9 {
+                    break;
+                }
+            }
+        }
+    }
+
+    for i in 0..rows {
+        // Left to right.
+        scan(map, &mut visible, (0..cols).map(|j| (i, j)));
+        // Right to left.
+        scan(map, &mut visible, (0..cols).rev().map(|j| (i, j)));
+    }
+    for j in 0..cols {
+        // Top to bottom.
+        scan(map, &mut visible, (0..rows).map(|i| (i, j)));
+        // Bottom to top.
+        scan(map, &mut visible, (0..rows).rev().map(|i| (i, j)));
+    }
+
+    visible
+}
+
 pub fn count_visible_trees_from_edges(filename: String) -> usize {
     let map = parse_map(filename);
-    let rows = 0..map.rows();
-    let cols = 0..map.cols();
-
-    fn visible_trees_from_edge<T, U>(map: &Grid<i8>, rows: T, cols: U) -> Vec<(usize, usize)>
-    where
-        T: IntoIterator<Item = usize>,
-        U: IntoIterator<Item = usize> + Clone,
-    {
-        let mut visible = Vec::new();
-        for i in rows {
-            let mut tallest: i8 = -1;
-            for j in cols.clone() {
-                let current = *map.get(i, j).unwrap();
-                if current > tallest {
-                    tallest = current;
-                    visible.push((i, j));
-                }
+    visible_from_edges(&map)
+        .iter()
+        .filter(|visible| **visible)
+        .count()
+}
+
+/// Computes every tree's scenic score. Looking along each row and column, a monotonic stack holds
+/// the trees not yet blocked by a taller one, so each tree's blocker is found in amortized O(1).
+pub fn scenic_scores(map: &Grid<i8>) -> Grid<usize> {
+    let (rows, cols) = map.size();
+    let mut scores = Grid::init(rows, cols, 1);
+
+    // Multiplies each tree's score by its viewing distance looking back along `line`.
+    fn look_back(
+        map: &Grid<i8>,
+        scores: &mut Grid<usize>,
+        line: impl Iterator<Item = (usize, usize)>,
+    ) {
+        let mut stack: Vec<(usize, i8)> = Vec::new();
+        for (k, (i, j)) in line.enumerate() {
+            let height = map[i][j];
+            while matches!(stack.last(), Some((_, top)) if *top < height) {
+                stack.pop();
             }
+            scores[i][j] *= match stack.last() {
+                Some((blocker, _)) => k - blocker,
+                None => k,
+            };
+            stack.push((k, height));
         }
-        visible
     }
 
-    fn rev(vec: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
-        vec.iter().map(|(i, j)| (*j, *i)).collect_vec()
+    for i in 0..rows {
+        // Looking left.
+        look_back(map, &mut scores, (0..cols).map(|j| (i, j)));
+        // Looking right.
+        look_back(map, &mut scores, (0..cols).rev().map(|j| (i, j)));
+    }
+    for j in 0..cols {
+        // Looking up.
+        look_back(map, &mut scores, (0..rows).map(|i| (i, j)));
+        // Looking down.
+        look_back(map, &mut scores, (0..rows).rev().map(|i| (i, j)));
+    }
+
+    scores
+}
+
+/// Position of the highest score, the first one in row-major order on ties.
+pub fn best_scenic_position(scores: &Grid<usize>) -> Option<(usize, usize)> {
+    let cols = scores.cols();
+    scores
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, score)| **score)
+        .map(|(index, _)| (index / cols, index % cols))
+}
+
+/// Directions an observer looks in, as (row, column) steps.
+#[derive(Clone, Copy, Debug)]
+pub enum Directions {
+    Four,
+    Eight,
+}
+
+impl Directions {
+    fn steps(self) -> &'static [(isize, isize)] {
+        match self {
+            Directions::Four => &[(-1, 0), (0, 1), (1, 0), (0, -1)],
+            Directions::Eight => &[
+                (-1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+                (1, 0),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+            ],
+        }
     }
+}
 
-    let mut visible: HashSet<(usize, usize)> = HashSet::new();
+/// Cells on the line between two points, both included, following Bresenham's algorithm.
+fn bresenham(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (mut row, mut col) = from;
+    let (d_row, d_col) = (-(to.0 - row).abs(), (to.1 - col).abs());
+    let (step_row, step_col) = ((to.0 - row).signum(), (to.1 - col).signum());
+    let mut error = d_col + d_row;
+    let mut cells = vec![(row, col)];
+    while (row, col) != to {
+        let doubled = 2 * error;
+        if doubled >= d_row {
+            error += d_row;
+            col += step_col;
+        }
+        if doubled <= d_col {
+            error += d_col;
+            row += step_row;
+        }
+        cells.push((row, col));
+    }
+    cells
+}
 
-    // Left to right.
-    visible.extend(visible_trees_from_edge(&map, rows.clone(), cols.clone()));
+fn eye_height(map: &Grid<i8>, viewpoint: (usize, usize), height: Option<i8>) -> i8 {
+    height.unwrap_or(map[viewpoint.0][viewpoint.1])
+}
 
-    // Top to bottom.
-    visible.extend(rev(visible_trees_from_edge(
-        &map.transpose(),
-        rows.clone(),
-        cols.clone(),
-    )));
+/// Whether the tree at `to` can be seen from `viewpoint`, that is, no tree strictly between them
+/// on the Bresenham line is as tall as the observer's eyes. These default to the viewpoint tree's
+/// height.
+pub fn line_of_sight(
+    map: &Grid<i8>,
+    viewpoint: (usize, usize),
+    to: (usize, usize),
+    height: Option<i8>,
+) -> bool {
+    let eye = eye_height(map, viewpoint, height);
+    let cells = bresenham(
+        (viewpoint.0 as isize, viewpoint.1 as isize),
+        (to.0 as isize, to.1 as isize),
+    );
+    cells.len() < 3
+        || cells[1..cells.len() - 1]
+            .iter()
+            .all(|(i, j)| map[*i as usize][*j as usize] < eye)
+}
 
-    // Right to left.
-    visible.extend(visible_trees_from_edge(
-        &map,
-        rows.clone(),
-        cols.clone().rev(),
-    ));
+/// Trees seen from `viewpoint` looking along the ray through `direction`, up to and including
+/// the first one as tall as the observer's eyes.
+pub fn visible_along_ray(
+    map: &Grid<i8>,
+    viewpoint: (usize, usize),
+    direction: (isize, isize),
+    height: Option<i8>,
+) -> Vec<(usize, usize)> {
+    assert!(direction != (0, 0), "Direction must not be null");
+    let eye = eye_height(map, viewpoint, height);
+    let from = (viewpoint.0 as isize, viewpoint.1 as isize);
+    let reach = (map.rows() + map.cols()) as isize;
+    let to = (from.0 + direction.0 * reach, from.1 + direction.1 * reach);
 
-    // Bottom to top.
-    visible.extend(rev(visible_trees_from_edge(
-        &map.transpose(),
-        rows,
-        cols.rev(),
-    )));
+    let mut visible = Vec::new();
+    for (i, j) in bresenham(from, to).into_iter().skip(1) {
+        match map.get(i as usize, j as usize) {
+            Some(tree) if i >= 0 && j >= 0 => {
+                visible.push((i as usize, j as usize));
+                if *tree >= eye {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    visible
+}
 
-    visible.len()
+/// Trees seen from `viewpoint` looking along each of the compass `directions`.
+pub fn visible_from(
+    map: &Grid<i8>,
+    viewpoint: (usize, usize),
+    height: Option<i8>,
+    directions: Directions,
+) -> Vec<(usize, usize)> {
+    directions
+        .steps()
+        .iter()
+        .flat_map(|direction| visible_along_ray(map, viewpoint, *direction, height))
+        .collect()
 }
 
 pub fn highest_tree_scenic_score(filename: String) -> usize {
     let map = parse_map(filename);
-    let mut scores = Grid::new(map.rows(), map.cols());
+    let scores = scenic_scores(&map);
+    let (row, col) = best_scenic_position(&scores).unwrap();
+    scores[row][col]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part1() {
+        let result = count_visible_trees_from_edges("assets/example.txt".to_string());
+        assert_eq!(result, 21);
+    }
+
+    fn synthetic_map(rows: usize, cols: usize) -> Grid<i8> {
+        let mut seed: u64 = 42;
+        let heights = (0..rows * cols)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ((seed >> 33) % 10) as i8
+            })
+            .collect_vec();
+        Grid::from_vec(heights, cols)
+    }
+
+    #[test]
+    fn visible_from_edges_synthetic() {
+        let map = synthetic_map(120, 90);
+        let visible = visible_from_edges(&map);
+        for i in 0..map.rows() {
+            for j in 0..map.cols() {
+                let height = map[i][j];
+                let expected = (0..i).all(|k| map[k][j] < height)
+                    || (i + 1..map.rows()).all(|k| map[k][j] < height)
+                    || (0..j).all(|k| map[i][k] < height)
+                    || (j + 1..map.cols()).all(|k| map[i][k] < height);
+                assert_eq!(visible[i][j], expected, "({}, {})", i, j);
+            }
+        }
+    }
 
     fn scenic_score(map: &Grid<i8>, row: usize, col: usize) -> usize {
         let mut score = 1;
@@ -126,23 +325,59 @@ pub fn highest_tree_scenic_score(filename: String) -> usize {
         score
     }
 
-    for i in 0..map.rows() {
-        for j in 0..map.cols() {
-            scores[i][j] = scenic_score(&map, i, j);
+    #[test]
+    fn scenic_scores_synthetic() {
+        let map = synthetic_map(60, 45);
+        let scores = scenic_scores(&map);
+        for i in 0..map.rows() {
+            for j in 0..map.cols() {
+                assert_eq!(scores[i][j], scenic_score(&map, i, j), "({}, {})", i, j);
+            }
         }
     }
 
-    *scores.iter().max().unwrap()
-}
+    #[test]
+    fn visible_from_viewpoint() {
+        let map = parse_map("assets/example.txt".to_string());
+        assert_eq!(
+            visible_from(&map, (3, 2), None, Directions::Four),
+            vec![(2, 2), (1, 2), (3, 3), (3, 4), (4, 2), (3, 1), (3, 0)]
+        );
+        assert_eq!(
+            visible_from(&map, (3, 2), Some(9), Directions::Eight).len(),
+            3 + 2 + 2 + 1 + 1 + 1 + 2 + 2
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let map = synthetic_map(30, 25);
+        for i in 0..map.rows() {
+            for j in 0..map.cols() {
+                let score: usize = Directions::Four
+                    .steps()
+                    .iter()
+                    .map(|direction| visible_along_ray(&map, (i, j), *direction, None).len())
+                    .product();
+                assert_eq!(score, scenic_score(&map, i, j), "({}, {})", i, j);
+            }
+        }
+    }
 
     #[test]
-    fn part1() {
-        let result = count_visible_trees_from_edges("assets/example.txt".to_string());
-        assert_eq!(result, 21);
+    fn line_of_sight_rays() {
+        let map = parse_map("assets/example.txt".to_string());
+        assert!(!line_of_sight(&map, (4, 0), (0, 4), None));
+        assert!(line_of_sight(&map, (2, 0), (2, 4), None));
+        assert!(line_of_sight(&map, (4, 0), (0, 4), Some(6)));
+        assert_eq!(
+            bresenham((0, 0), (2, 4)),
+            vec![(0, 0), (1, 1), (1, 2), (2, 3), (2, 4)]
+        );
+        assert_eq!(visible_along_ray(&map, (4, 0), (-1, 2), None), vec![(3, 1)]);
+    }
+
+    #[test]
+    fn best_position() {
+        let map = parse_map("assets/example.txt".to_string());
+        assert_eq!(best_scenic_position(&scenic_scores(&map)), Some((3, 2)));
     }
 
     #[test]
@@ -1,6 +1,13 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::fs;
-use vec1::Vec1;
+use std::hash::Hash;
+
+mod physics;
+mod recorder;
+
+pub use physics::{Link, Physics};
+pub use recorder::{Recorder, Recording};
 
 macro_rules! pos {
     ($x:expr, $y:expr) => {
@@ -8,160 +15,280 @@ macro_rules! pos {
     };
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-enum Direction {
-    Up,
-    Right,
-    Down,
-    Left,
+/// A point on a lattice, with one coordinate per axis.
+pub trait Lattice: Copy + Eq + Hash + Debug {
+    const AXES: usize;
+    const ORIGIN: Self;
+
+    /// Axis and sign of a motion letter such as `U`.
+    fn letter(letter: char) -> Option<(usize, isize)>;
+
+    fn axis(&self, axis: usize) -> isize;
+
+    fn axis_mut(&mut self, axis: usize) -> &mut isize;
+
+    /// Unit step of a motion combining letters along different axes, such as `UL`.
+    fn direction(name: &str) -> Option<Self> {
+        let mut step = Self::ORIGIN;
+        for letter in name.chars() {
+            let (axis, sign) = Self::letter(letter)?;
+            if step.axis(axis) != 0 {
+                return None;
+            }
+            *step.axis_mut(axis) = sign;
+        }
+        if step == Self::ORIGIN {
+            return None;
+        }
+        Some(step)
+    }
+
+    fn offset(&mut self, step: Self) {
+        for axis in 0..Self::AXES {
+            *self.axis_mut(axis) += step.axis(axis);
+        }
+    }
+
+    /// The step going from this point to `to`.
+    fn delta(&self, to: Self) -> Self {
+        let mut delta = to;
+        for axis in 0..Self::AXES {
+            *delta.axis_mut(axis) -= self.axis(axis);
+        }
+        delta
+    }
+
+    /// Moves one step towards `head` when further than `slack` along any axis. Going
+    /// `orthogonal`, it instead takes steps along the axis with the largest distance, the first one
+    /// on ties, until back within `slack`, which may take a step along each axis when `head` moved
+    /// diagonally. Only where it ends up counts as visited.
+    fn follow(&mut self, head: Self, slack: usize, orthogonal: bool) -> bool {
+        let within =
+            |delta: Self| (0..Self::AXES).all(|axis| delta.axis(axis).unsigned_abs() <= slack);
+        let mut delta = self.delta(head);
+        if within(delta) {
+            return false;
+        }
+        if orthogonal {
+            while !within(delta) {
+                let axis = (0..Self::AXES)
+                    .max_by_key(|axis| (delta.axis(*axis).abs(), std::cmp::Reverse(*axis)))
+                    .unwrap();
+                *self.axis_mut(axis) += delta.axis(axis).signum();
+                delta = self.delta(head);
+            }
+        } else {
+            for axis in 0..Self::AXES {
+                *self.axis_mut(axis) += delta.axis(axis).signum();
+            }
+        }
+        true
+    }
 }
 
+/// A point on the plane, with `y` growing downwards. Moves go `U`p, `D`own, `L`eft, `R`ight or
+/// diagonally, such as `UL`.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-struct Position {
-    x: isize,
-    y: isize,
+pub struct Position {
+    pub x: isize,
+    pub y: isize,
 }
 
-#[derive(Clone)]
-struct Segment {
-    head_pos: Position,
-    tail_pos: Position,
-    head_visited: HashSet<Position>,
-    tail_visited: HashSet<Position>,
+/// A point on a cubic lattice, with `y` growing downwards and `z` forwards. Moves go `U`p,
+/// `D`own, `L`eft, `R`ight, `F`orward, `B`ackward or any combination along different axes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position3 {
+    pub x: isize,
+    pub y: isize,
+    pub z: isize,
 }
 
-struct Rope {
-    segments: Vec1<Segment>,
-}
+impl Lattice for Position {
+    const AXES: usize = 2;
+    const ORIGIN: Self = pos!(0, 0);
 
-impl Position {
-    fn positions_around(&self) -> [Position; 8] {
-        [
-            pos!(self.x + 1, self.y),
-            pos!(self.x + 1, self.y + 1),
-            pos!(self.x, self.y + 1),
-            pos!(self.x - 1, self.y + 1),
-            pos!(self.x - 1, self.y),
-            pos!(self.x - 1, self.y - 1),
-            pos!(self.x, self.y - 1),
-            pos!(self.x + 1, self.y - 1),
-        ]
+    fn letter(letter: char) -> Option<(usize, isize)> {
+        match letter {
+            'L' => Some((0, -1)),
+            'R' => Some((0, 1)),
+            'U' => Some((1, -1)),
+            'D' => Some((1, 1)),
+            _ => None,
+        }
     }
 
-    fn manhattan_distance(&self, other: &Position) -> isize {
-        (self.x - other.x).abs() + (self.y - other.y).abs()
+    fn axis(&self, axis: usize) -> isize {
+        [self.x, self.y][axis]
     }
-}
 
-impl Segment {
-    fn new() -> Segment {
-        Segment {
-            head_pos: pos!(0, 0),
-            tail_pos: pos!(0, 0),
-            head_visited: HashSet::from([pos!(0, 0)]),
-            tail_visited: HashSet::from([pos!(0, 0)]),
+    fn axis_mut(&mut self, axis: usize) -> &mut isize {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("No axis {} on the plane", axis),
         }
     }
+}
 
-    fn move_to(&mut self, x: isize, y: isize) {
-        self.head_pos = pos!(x, y);
-        self.head_visited.insert(self.head_pos);
+impl Lattice for Position3 {
+    const AXES: usize = 3;
+    const ORIGIN: Self = Position3 { x: 0, y: 0, z: 0 };
 
-        // Move tail.
-        if self.tail_pos == self.head_pos {
-            return;
-        }
-        let positions_around_head = self.head_pos.positions_around();
-        if positions_around_head.contains(&self.tail_pos) {
-            return;
+    fn letter(letter: char) -> Option<(usize, isize)> {
+        match letter {
+            'F' => Some((2, 1)),
+            'B' => Some((2, -1)),
+            letter => Position::letter(letter),
         }
+    }
 
-        let pos = positions_around_head
-            .iter()
-            .filter(|pos| {
-                if self.tail_pos.x == self.head_pos.x || self.tail_pos.y == self.head_pos.y {
-                    // Move straight.
-                    pos.x == self.tail_pos.x || pos.y == self.tail_pos.y
-                } else {
-                    // Move diagonally.
-                    pos.x != self.tail_pos.x && pos.y != self.tail_pos.y
-                }
-            })
-            .min_by_key(|pos| self.tail_pos.manhattan_distance(pos))
-            .unwrap();
-        self.tail_pos = *pos;
-        self.tail_visited.insert(self.tail_pos);
+    fn axis(&self, axis: usize) -> isize {
+        [self.x, self.y, self.z][axis]
+    }
+
+    fn axis_mut(&mut self, axis: usize) -> &mut isize {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("No axis {} on the cubic lattice", axis),
+        }
     }
 }
 
-impl Rope {
-    fn new(size: usize) -> Rope {
+/// Knot positions from head to tail, along with how often tracked knots visited each position.
+struct Rope<P: Lattice = Position> {
+    knots: Vec<P>,
+    links: Vec<Link>,
+    visits: Vec<Option<HashMap<P, usize>>>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct KnotStats<P: Lattice = Position> {
+    /// Index of the knot, the head being 0.
+    pub knot: usize,
+    pub visited: usize,
+    /// Lowest and highest corners of the visited area, top-left and bottom-right on the plane.
+    pub bounds: (P, P),
+    /// The position visited the most, the top-most then left-most on ties, and its visit count.
+    pub most_visited: (P, usize),
+}
+
+impl<P: Lattice> Rope<P> {
+    /// Creates a rope tracking the visits of `knots` only.
+    fn tracking(physics: &Physics, knots: &[usize]) -> Rope<P> {
+        let segment_count = physics.segment_count();
+        let mut visits = vec![None; segment_count + 1];
+        for knot in knots {
+            visits[*knot] = Some(HashMap::from([(P::ORIGIN, 1)]));
+        }
         Rope {
-            segments: Vec1::try_from_vec(vec![Segment::new(); size]).unwrap(),
+            knots: vec![P::ORIGIN; segment_count + 1],
+            links: physics.links.clone(),
+            visits,
         }
     }
 
-    fn head_pos(&self) -> Position {
-        self.segments.first().head_pos
+    fn head_pos(&self) -> P {
+        self.knots[0]
     }
 
-    fn tail_pos(&self) -> Position {
-        self.segments.last().tail_pos
+    fn tail_pos(&self) -> P {
+        self.knots[self.knots.len() - 1]
     }
 
-    fn head_visited(&self) -> &HashSet<Position> {
-        &self.segments.first().head_visited
+    /// Positions visited by the tail, if tracked.
+    fn tail_visited(&self) -> Option<&HashMap<P, usize>> {
+        self.visits[self.visits.len() - 1].as_ref()
     }
 
-    fn tail_visited(&self) -> &HashSet<Position> {
-        &self.segments.last().tail_visited
+    fn visit(&mut self, knot: usize) {
+        if let Some(visits) = &mut self.visits[knot] {
+            *visits.entry(self.knots[knot]).or_insert(0) += 1;
+        }
     }
 
-    fn move_facing(&mut self, direction: Direction) {
-        let mut segments_iter = self.segments.iter_mut();
-        let mut current = segments_iter.next().unwrap();
-        let position = match direction {
-            Direction::Up => pos!(current.head_pos.x, current.head_pos.y - 1),
-            Direction::Right => pos!(current.head_pos.x + 1, current.head_pos.y),
-            Direction::Down => pos!(current.head_pos.x, current.head_pos.y + 1),
-            Direction::Left => pos!(current.head_pos.x - 1, current.head_pos.y),
-        };
-        current.move_to(position.x, position.y);
+    /// Statistics of a knot, if its visits are tracked.
+    fn stats(&self, knot: usize) -> Option<KnotStats<P>> {
+        let visits = self.visits.get(knot)?.as_ref()?;
+        let (mut min, mut max) = (P::ORIGIN, P::ORIGIN);
+        for axis in 0..P::AXES {
+            *min.axis_mut(axis) = visits.keys().map(|pos| pos.axis(axis)).min().unwrap();
+            *max.axis_mut(axis) = visits.keys().map(|pos| pos.axis(axis)).max().unwrap();
+        }
+        // Later axes take precedence on ties, so that rows come before columns.
+        let most_visited = visits
+            .iter()
+            .min_by_key(|(pos, count)| {
+                let axes = (0..P::AXES).rev().map(|axis| pos.axis(axis));
+                (std::cmp::Reverse(**count), axes.collect::<Vec<isize>>())
+            })
+            .map(|(pos, count)| (*pos, *count))
+            .unwrap();
+        Some(KnotStats {
+            knot,
+            visited: visits.len(),
+            bounds: (min, max),
+            most_visited,
+        })
+    }
 
-        for segment in segments_iter {
-            segment.move_to(current.tail_pos.x, current.tail_pos.y);
-            current = segment
+    fn move_by(&mut self, step: P) {
+        self.knots[0].offset(step);
+        self.visit(0);
+
+        // Once a knot stays put, so do all the ones after it.
+        let mut step = step;
+        for i in 1..self.knots.len() {
+            let head = self.knots[i - 1];
+            let knot = &mut self.knots[i];
+            let before = *knot;
+            let moved = match self.links[i - 1] {
+                Link::Loose { slack, orthogonal } => knot.follow(head, slack, orthogonal),
+                Link::Rigid => {
+                    knot.offset(step);
+                    true
+                }
+            };
+            if !moved {
+                return;
+            }
+            step = before.delta(*knot);
+            self.visit(i);
         }
     }
 }
 
-impl std::fmt::Display for Rope {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let key_x = |pos: &&Position| pos.x;
-        let key_y = |pos: &&Position| pos.y;
-        let min_x = self.head_visited().iter().min_by_key(key_x).unwrap().x;
-        let max_x = self.head_visited().iter().max_by_key(key_x).unwrap().x;
-        let min_y = self.head_visited().iter().min_by_key(key_y).unwrap().y;
-        let max_y = self.head_visited().iter().max_by_key(key_y).unwrap().y;
-        for i in min_y..=max_y {
-            for j in min_x..=max_x {
+impl Rope {
+    /// Draws the area between the `min` and `max` corners, with the positions visited by the tail
+    /// when `trail` is set and the tail is tracked.
+    fn draw(
+        &self,
+        f: &mut impl std::fmt::Write,
+        (min, max): (Position, Position),
+        trail: bool,
+    ) -> std::fmt::Result {
+        for i in min.y..=max.y {
+            for j in min.x..=max.x {
                 let pos = pos!(j, i);
                 if self.head_pos() == pos {
                     write!(f, "H")?;
                 } else {
-                    let knot = self
-                        .segments
+                    let knot = self.knots[..self.knots.len() - 1]
                         .iter()
-                        .enumerate()
-                        .find(|(_, segment)| segment.head_pos == pos);
+                        .position(|knot| *knot == pos);
                     match knot {
-                        Some((index, _)) => write!(f, "{}", index),
+                        Some(index) => write!(f, "{}", index),
                         None => {
                             if self.tail_pos() == pos {
                                 write!(f, "T")
                             } else if i == 0 && j == 0 {
                                 write!(f, "s")
-                            } else if self.tail_visited().contains(&pos) {
+                            } else if trail
+                                && self
+                                    .tail_visited()
+                                    .is_some_and(|tail| tail.contains_key(&pos))
+                            {
                                 write!(f, "#")
                             } else {
                                 write!(f, ".")
@@ -176,33 +303,108 @@ impl std::fmt::Display for Rope {
     }
 }
 
-fn parse_rope(filename: String, segment_count: usize) -> Rope {
+/// Draws the area visited by the head, which must be tracked.
+impl std::fmt::Display for Rope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stats = self.stats(0).expect("Head visits are not tracked");
+        self.draw(f, stats.bounds, true)
+    }
+}
+
+/// Parses the optional links header followed by the motions.
+fn parse_input<P: Lattice>(filename: String) -> (Option<Physics>, Vec<(P, usize)>) {
     let input = fs::read_to_string(filename).unwrap();
-    let lines = input.lines();
-
-    let mut rope = Rope::new(segment_count);
-    for line in lines {
-        let mut parts = line.split_whitespace();
-        let direction = match parts.next() {
-            Some("U") => Direction::Up,
-            Some("R") => Direction::Right,
-            Some("D") => Direction::Down,
-            Some("L") => Direction::Left,
-            Some(direction) => panic!("Unsupported direction: {}", direction),
-            None => panic!("Missing direction"),
-        };
-        let steps = parts.next().unwrap().parse::<isize>().unwrap();
-
-        for _ in 0..steps {
-            rope.move_facing(direction);
+    let mut lines = input.lines().peekable();
+    let physics = match lines.peek() {
+        Some(line) if line.starts_with("links") => {
+            Some(lines.next().unwrap().parse::<Physics>().unwrap())
+        }
+        _ => None,
+    };
+    let motions = lines
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            let direction = match parts.next() {
+                Some(direction) => match P::direction(direction) {
+                    Some(step) => step,
+                    None => panic!("Unsupported direction: {}", direction),
+                },
+                None => panic!("Missing direction"),
+            };
+            let steps = parts.next().unwrap().parse::<usize>().unwrap();
+            (direction, steps)
+        })
+        .collect();
+    (physics, motions)
+}
+
+/// The physics from the input's header, which must have `segment_count` links, or the puzzle's.
+fn physics_for(header: Option<Physics>, segment_count: usize) -> Physics {
+    match header {
+        Some(physics) if physics.segment_count() != segment_count => panic!(
+            "Header has {} links for a rope of {} segments",
+            physics.segment_count(),
+            segment_count
+        ),
+        Some(physics) => physics,
+        None => Physics::uniform(segment_count),
+    }
+}
+
+fn simulate<P: Lattice>(motions: &[(P, usize)], physics: &Physics, knots: &[usize]) -> Rope<P> {
+    let mut rope = Rope::tracking(physics, knots);
+    for (step, steps) in motions {
+        for _ in 0..*steps {
+            rope.move_by(*step);
         }
     }
-    return rope;
+    rope
+}
+
+fn parse_rope_tracking<P: Lattice>(
+    filename: String,
+    segment_count: usize,
+    knots: &[usize],
+) -> Rope<P> {
+    let (header, motions) = parse_input(filename);
+    simulate(&motions, &physics_for(header, segment_count), knots)
 }
 
 pub fn count_tail_visited_positions(filename: String, segment_count: usize) -> usize {
-    let rope = parse_rope(filename, segment_count);
-    rope.tail_visited().len()
+    count_tail_visited::<Position>(filename, segment_count)
+}
+
+/// Counts the positions visited by the tail of a rope moving on any lattice.
+pub fn count_tail_visited<P: Lattice>(filename: String, segment_count: usize) -> usize {
+    let rope = parse_rope_tracking::<P>(filename, segment_count, &[segment_count]);
+    rope.tail_visited().unwrap().len()
+}
+
+/// Counts the positions visited by the tail of a rope with the given physics, regardless of the
+/// input's header.
+pub fn count_tail_visited_with<P: Lattice>(filename: String, physics: &Physics) -> usize {
+    let (_, motions) = parse_input::<P>(filename);
+    let rope = simulate(&motions, physics, &[physics.segment_count()]);
+    rope.tail_visited().unwrap().len()
+}
+
+/// Statistics of the given knots, or of every knot when `None`. Only the visits of those knots
+/// are tracked along the way.
+pub fn knot_stats<P: Lattice>(
+    filename: String,
+    segment_count: usize,
+    knots: Option<&[usize]>,
+) -> Vec<KnotStats<P>> {
+    let all = (0..=segment_count).collect::<Vec<usize>>();
+    let knots = knots.unwrap_or(&all);
+    if let Some(knot) = knots.iter().find(|knot| **knot > segment_count) {
+        panic!("No knot {} in a rope of {} segments", knot, segment_count);
+    }
+    let rope = parse_rope_tracking(filename, segment_count, knots);
+    knots
+        .iter()
+        .map(|knot| rope.stats(*knot).unwrap())
+        .collect()
 }
 
 #[cfg(test)]
@@ -210,6 +412,10 @@ mod tests {
     use super::*;
     use test_case::test_case;
 
+    fn parse_rope(filename: String, segment_count: usize) -> Rope {
+        parse_rope_tracking(filename, segment_count, &[0, segment_count])
+    }
+
     #[test_case(
         "assets/example_small.txt",
         1,
@@ -272,6 +478,102 @@ T.........................
         assert_eq!(count, expected)
     }
 
+    #[test_case("assets/example_diagonal.txt", 1, 17)]
+    #[test_case("assets/example_diagonal.txt", 9, 1)]
+    #[test_case("assets/example_diagonal.txt", 3, 6)]
+    fn diagonal(filename: &str, segment_count: usize, expected: usize) {
+        let count = count_tail_visited::<Position>(filename.to_string(), segment_count);
+        assert_eq!(count, expected);
+    }
+
+    #[test_case("assets/example_3d.txt", 1, 17)]
+    #[test_case("assets/example_3d.txt", 4, 4)]
+    #[test_case("assets/example_small.txt", 1, 13)]
+    fn cubic(filename: &str, segment_count: usize, expected: usize) {
+        let count = count_tail_visited::<Position3>(filename.to_string(), segment_count);
+        assert_eq!(count, expected);
+    }
+
+    #[test_case("links 2", 7)]
+    #[test_case("links 1:orthogonal", 10)]
+    #[test_case("links 1 rigid", 13)]
+    #[test_case("links rigid rigid rigid", 21)]
+    fn physics(header: &str, expected: usize) {
+        let physics = header.parse::<Physics>().unwrap();
+        let count =
+            count_tail_visited_with::<Position>("assets/example_small.txt".to_string(), &physics);
+        assert_eq!(count, expected);
+    }
+
+    #[test_case("links 1:orthogonal", 15)]
+    #[test_case("links 1:orthogonal 1:orthogonal 1:orthogonal", 4)]
+    fn physics_diagonal(header: &str, expected: usize) {
+        let physics = header.parse::<Physics>().unwrap();
+        let count = count_tail_visited_with::<Position>(
+            "assets/example_diagonal.txt".to_string(),
+            &physics,
+        );
+        assert_eq!(count, expected);
+    }
+
+    #[test]
+    fn orthogonal_follows_diagonal() {
+        let mut knot = pos!(0, 0);
+        assert!(knot.follow(pos!(2, 2), 1, true));
+        assert_eq!(knot, pos!(1, 1));
+        assert!(knot.follow(pos!(4, 2), 1, true));
+        assert_eq!(knot, pos!(3, 1));
+        assert!(!knot.follow(pos!(4, 2), 1, true));
+    }
+
+    #[test]
+    fn physics_header() {
+        let count = count_tail_visited::<Position>("assets/example_links.txt".to_string(), 4);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Header has 4 links for a rope of 9 segments")]
+    fn physics_header_mismatch() {
+        count_tail_visited::<Position>("assets/example_links.txt".to_string(), 9);
+    }
+
+    #[test_case("F")]
+    #[test_case("UD")]
+    #[test_case("")]
+    fn invalid_direction(name: &str) {
+        assert_eq!(Position::direction(name), None);
+    }
+
+    #[test]
+    fn knot_stats() {
+        let stats = super::knot_stats("assets/example_small.txt".to_string(), 9, None);
+        assert_eq!(stats.len(), 10);
+        assert_eq!(
+            stats[1],
+            KnotStats {
+                knot: 1,
+                visited: 13,
+                bounds: (pos!(0, -4), pos!(4, 0)),
+                most_visited: (pos!(4, -3), 2),
+            }
+        );
+        assert_eq!(stats[3].visited, 4);
+        assert_eq!(stats[9].bounds, (pos!(0, 0), pos!(0, 0)));
+
+        let stats = super::knot_stats("assets/example_small.txt".to_string(), 9, Some(&[2]));
+        assert_eq!(stats.len(), 1);
+        assert_eq!(
+            stats[0],
+            KnotStats {
+                knot: 2,
+                visited: 7,
+                bounds: (pos!(0, -3), pos!(4, 0)),
+                most_visited: (pos!(3, -3), 1),
+            }
+        );
+    }
+
     #[test]
     fn part2() {
         let count = count_tail_visited_positions("assets/example_large.txt".to_string(), 9);
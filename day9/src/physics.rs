@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+/// How a knot follows the knot ahead of it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Link {
+    /// Follows once further than `slack` along any axis, with one diagonal step, or when
+    /// `orthogonal` with single-axis steps until back within `slack`.
+    Loose { slack: usize, orthogonal: bool },
+    /// Moves in lockstep with the knot ahead.
+    Rigid,
+}
+
+/// The links of a rope, from the one following the head to the one leading to the tail.
+///
+/// Inputs can set it in a header line listing every link, such as `links 1 2:orthogonal rigid`,
+/// where numbers are the slack of loose links.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Physics {
+    pub links: Vec<Link>,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Link::Loose {
+            slack: 1,
+            orthogonal: false,
+        }
+    }
+}
+
+impl Physics {
+    /// The puzzle's physics, where knots follow diagonally as soon as they stop touching.
+    pub fn uniform(segment_count: usize) -> Physics {
+        Physics {
+            links: vec![Link::default(); segment_count],
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.links.len()
+    }
+}
+
+impl FromStr for Link {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "rigid" {
+            return Ok(Link::Rigid);
+        }
+        let (slack, orthogonal) = match s.split_once(':') {
+            Some((slack, "orthogonal")) => (slack, true),
+            Some((_, following)) => return Err(format!("Unsupported following: {}", following)),
+            None => (s, false),
+        };
+        match slack.parse::<usize>() {
+            Ok(slack) if slack > 0 => Ok(Link::Loose { slack, orthogonal }),
+            _ => Err(format!("Invalid slack: {}", slack)),
+        }
+    }
+}
+
+impl FromStr for Physics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        if parts.next() != Some("links") {
+            return Err(format!("Not a links header: {}", s));
+        }
+        let links = parts
+            .map(Link::from_str)
+            .collect::<Result<Vec<Link>, String>>()?;
+        Ok(Physics { links })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "links 1 2:orthogonal rigid".parse(),
+            Ok(Physics {
+                links: vec![
+                    Link::default(),
+                    Link::Loose {
+                        slack: 2,
+                        orthogonal: true
+                    },
+                    Link::Rigid,
+                ]
+            })
+        );
+        assert_eq!("links".parse(), Ok(Physics::uniform(0)));
+    }
+
+    #[test_case("links 0", "Invalid slack: 0")]
+    #[test_case("links 1:straight", "Unsupported following: straight")]
+    #[test_case("R 4", "Not a links header: R 4")]
+    fn parse_error(header: &str, expected: &str) {
+        assert_eq!(header.parse::<Physics>(), Err(expected.to_string()));
+    }
+}
@@ -0,0 +1,153 @@
+use super::{parse_input, physics_for, simulate, Position, Rope};
+use std::io::{self, Write};
+
+/// Replays a rope's motions and draws it every few steps.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Recorder {
+    /// Top-left and bottom-right corners of the area to draw, or `None` for the whole area visited
+    /// by the head.
+    pub viewport: Option<(Position, Position)>,
+    /// Steps to skip between frames. The last step is always recorded.
+    pub frame_skip: usize,
+    /// Whether to overlay the positions visited by the tail so far.
+    pub trail: bool,
+}
+
+/// Frames of a rope's motions, all of the same size.
+pub struct Recording {
+    pub width: usize,
+    pub height: usize,
+    pub frames: Vec<String>,
+}
+
+impl Recorder {
+    pub fn record(&self, filename: String, segment_count: usize) -> Recording {
+        let (header, motions) = parse_input::<Position>(filename);
+        let physics = physics_for(header, segment_count);
+        let viewport = match self.viewport {
+            Some(viewport) => viewport,
+            None => simulate(&motions, &physics, &[0]).stats(0).unwrap().bounds,
+        };
+
+        // The tail's visits only show in the trail.
+        let tail = if self.trail {
+            vec![segment_count]
+        } else {
+            vec![]
+        };
+        let mut rope = Rope::tracking(&physics, &tail);
+        let mut frames = Vec::new();
+        let mut draw = |rope: &Rope| {
+            let mut frame = String::new();
+            rope.draw(&mut frame, viewport, self.trail).unwrap();
+            frames.push(frame);
+        };
+        draw(&rope);
+        let mut step = 0;
+        for (direction, steps) in motions.iter() {
+            for _ in 0..*steps {
+                rope.move_by(*direction);
+                step += 1;
+                if step % (self.frame_skip + 1) == 0 {
+                    draw(&rope);
+                }
+            }
+        }
+        if step % (self.frame_skip + 1) != 0 {
+            draw(&rope);
+        }
+
+        let (min, max) = viewport;
+        Recording {
+            width: (max.x - min.x + 1).max(0) as usize,
+            height: (max.y - min.y + 1).max(0) as usize,
+            frames,
+        }
+    }
+}
+
+impl Recording {
+    /// Writes the frames as an asciinema v2 cast, each shown for `frame_duration` seconds.
+    pub fn write_cast(&self, mut writer: impl Write, frame_duration: f64) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}",
+            self.width, self.height
+        )?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let output = format!("\x1b[H\x1b[2J{}", frame.replace('\n', "\r\n"));
+            writeln!(
+                writer,
+                "[{:.3}, \"o\", {}]",
+                i as f64 * frame_duration,
+                json_string(&output)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output += "\\\"",
+            '\\' => output += "\\\\",
+            '\n' => output += "\\n",
+            '\r' => output += "\\r",
+            c if c.is_control() => output += format!("\\u{:04x}", c as u32).as_str(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let recorder = Recorder {
+            viewport: Some((Position { x: 0, y: -4 }, Position { x: 5, y: 0 })),
+            frame_skip: 4,
+            trail: true,
+        };
+        let recording = recorder.record("assets/example_small.txt".to_string(), 1);
+        // 24 steps make a frame every 5 steps plus the initial and last ones.
+        assert_eq!(recording.frames.len(), 6);
+        assert_eq!((recording.width, recording.height), (6, 5));
+        assert_eq!(
+            recording.frames[0],
+            "......\n......\n......\n......\nH.....\n"
+        );
+        assert_eq!(
+            recording.frames[5],
+            "..##..\n...##.\n.TH##.\n....#.\ns###..\n"
+        );
+
+        let recording = Recorder::default().record("assets/example_small.txt".to_string(), 1);
+        assert_eq!(recording.frames.len(), 25);
+        assert_eq!(
+            recording.frames[24],
+            "......\n......\n.TH...\n......\ns.....\n"
+        );
+    }
+
+    #[test]
+    fn cast() {
+        let recorder = Recorder {
+            frame_skip: 23,
+            ..Default::default()
+        };
+        let recording = recorder.record("assets/example_small.txt".to_string(), 1);
+        let mut data = Vec::new();
+        recording.write_cast(&mut data, 0.5).unwrap();
+        let cast = String::from_utf8(data).unwrap();
+        let lines = cast.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "{\"version\": 2, \"width\": 6, \"height\": 5}");
+        assert!(lines[2].starts_with("[0.500, \"o\", \"\\u001b[H\\u001b[2J......\\r\\n"));
+    }
+}